@@ -0,0 +1,246 @@
+use std::io::{self, Read, Result, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use mio::event::Event;
+use mio::net::TcpStream;
+use mio::{Events, Interest, Poll, Token};
+
+/// Not the entire url, but everyhing after the domain addr
+/// i.e. http://localhost/1000/hello => /1000/hello
+pub fn get_req(path: &str) -> String {
+    format!(
+        "GET {path} HTTP/1.1\r\n\
+             Host: localhost\r\n\
+             Connection: close\r\n\
+             \r\n"
+    )
+}
+
+/// Where a single request is in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Waiting for the connection to be established and the request to be
+    /// written in full.
+    Writing,
+    /// The request is sent, we're reading the response until EOF.
+    Reading,
+    Done,
+}
+
+struct Request {
+    path: String,
+    stream: TcpStream,
+    state: State,
+    request: Vec<u8>,
+    written: usize,
+    response: Vec<u8>,
+    started: Instant,
+}
+
+/// Reported once for every request when the server closes the connection.
+#[derive(Debug, Clone)]
+pub struct Completed {
+    pub token: Token,
+    pub path: String,
+    pub response: String,
+    pub elapsed: Duration,
+}
+
+/// Drives many HTTP GET requests concurrently on a single `mio::Poll`.
+///
+/// Every request gets its own `Token` (the index into `requests`), so an
+/// event always maps back to the stream it was reported for. The request is
+/// only written when the OS tells us the socket is writable, and we keep
+/// track of how much we've written since a `write` can be partial.
+pub struct MultiRequestDriver {
+    poll: Poll,
+    addr: SocketAddr,
+    requests: Vec<Request>,
+    remaining: usize,
+}
+
+impl MultiRequestDriver {
+    pub fn new(addr: impl ToSocketAddrs) -> Result<Self> {
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address"))?;
+
+        Ok(Self {
+            poll: Poll::new()?,
+            addr,
+            requests: vec![],
+            remaining: 0,
+        })
+    }
+
+    /// Starts a non-blocking connect and registers the stream. Nothing is
+    /// written until we get a writable event for it.
+    pub fn add_request(&mut self, path: &str) -> Result<Token> {
+        let token = Token(self.requests.len());
+        // ++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+        // Mio has its own `TcpStream`. Its `connect` doesn't block, which is
+        // why we have to wait for a writable event before sending the request
+        let mut stream = TcpStream::connect(self.addr)?;
+        // ++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+
+        // ++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+        // Slightly different arguments. `Token` is a wrapper so we just wrap the value
+        // Interests are expressed slightly different but boil down to the same
+        // arguments to `epoll_ctl`
+        self.poll.registry().register(
+            &mut stream,
+            token,
+            Interest::READABLE | Interest::WRITABLE,
+        )?;
+        // ++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+
+        self.requests.push(Request {
+            path: path.to_string(),
+            stream,
+            state: State::Writing,
+            request: get_req(path).into_bytes(),
+            written: 0,
+            response: vec![],
+            started: Instant::now(),
+        });
+        self.remaining += 1;
+
+        Ok(token)
+    }
+
+    /// Runs until every request has completed. `on_complete` is called as
+    /// soon as a response is finished, and all results are returned in the
+    /// order they completed.
+    pub fn run(&mut self, mut on_complete: impl FnMut(&Completed)) -> Result<Vec<Completed>> {
+        let mut completed = Vec::with_capacity(self.remaining);
+        // ++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+        // Mio has it's own collection type instead of Vec<Event>
+        let mut events = Events::with_capacity(10);
+        // ++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+
+        while self.remaining > 0 {
+            self.poll.poll(&mut events, None)?;
+
+            // We poll without a timeout, so no events is a spurious wakeup and
+            // the loop just polls again
+            for event in events.iter() {
+                if let Some(done) = self.handle_event(event)? {
+                    on_complete(&done);
+                    completed.push(done);
+                }
+            }
+        }
+
+        Ok(completed)
+    }
+
+    fn handle_event(&mut self, event: &Event) -> Result<Option<Completed>> {
+        // ++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+        // We need to extract the value we wrapped in Token
+        let token = event.token();
+        // ++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+        let registry = self.poll.registry();
+        let req = match self.requests.get_mut(token.0) {
+            Some(req) => req,
+            // Not one of ours, ignore it
+            None => return Ok(None),
+        };
+
+        if req.state == State::Writing && event.is_writable() {
+            // A writable event for a non-blocking connect means the connection
+            // either succeeded or failed. Surface the failure right away.
+            if let Some(e) = req.stream.take_error()? {
+                return Err(e);
+            }
+
+            while req.written < req.request.len() {
+                match req.stream.write(&req.request[req.written..]) {
+                    Ok(n) => req.written += n,
+                    // We'll get a new writable event when there is room in the
+                    // send buffer again
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    // Still connecting on some platforms
+                    Err(e) if e.kind() == io::ErrorKind::NotConnected => break,
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if req.written == req.request.len() {
+                // We're not interested in writable events any more
+                registry.reregister(&mut req.stream, token, Interest::READABLE)?;
+                req.state = State::Reading;
+            }
+        }
+
+        if req.state == State::Reading && event.is_readable() {
+            let mut data = vec![0u8; 4096];
+            loop {
+                match req.stream.read(&mut data) {
+                    Ok(0) => {
+                        registry.deregister(&mut req.stream)?;
+                        req.state = State::Done;
+                        self.remaining -= 1;
+
+                        return Ok(Some(Completed {
+                            token,
+                            path: req.path.clone(),
+                            response: String::from_utf8_lossy(&req.response).to_string(),
+                            elapsed: req.started.elapsed(),
+                        }));
+                    }
+                    Ok(n) => req.response.extend_from_slice(&data[..n]),
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn driver_completes_every_request() {
+        use std::{
+            io::{BufRead, BufReader},
+            net::TcpListener,
+            thread,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let n_requests = 3;
+
+        thread::spawn(move || {
+            for stream in listener.incoming().take(n_requests) {
+                let mut stream = stream.unwrap();
+                let mut line = String::new();
+                BufReader::new(&stream).read_line(&mut line).unwrap();
+                let path = line.split_whitespace().nth(1).unwrap().to_string();
+                stream
+                    .write_all(format!("HTTP/1.1 200 OK\r\n\r\n{path}").as_bytes())
+                    .unwrap();
+            }
+        });
+
+        let mut driver = MultiRequestDriver::new(addr).unwrap();
+        for i in 0..n_requests {
+            driver.add_request(&format!("/req-{i}")).unwrap();
+        }
+
+        let completed = driver.run(|_| ()).unwrap();
+        assert_eq!(completed.len(), n_requests);
+        for done in completed {
+            assert!(done.response.ends_with(&done.path), "{done:?}");
+            assert_eq!(done.path, format!("/req-{}", done.token.0));
+        }
+    }
+}
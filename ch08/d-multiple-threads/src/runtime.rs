@@ -3,6 +3,7 @@ pub use reactor::reactor;
 
 mod executor;
 mod reactor;
+mod task;
 
 pub fn init() -> Executor {
     reactor::start();
@@ -1,3 +1,4 @@
+use super::task::{self, JoinHandle};
 use crate::future::{Future, PollState};
 use std::{
     cell::{Cell, RefCell},
@@ -6,7 +7,7 @@ use std::{
     thread::{self, Thread},
 };
 
-type Task = Box<dyn Future<Output = String>>;
+type Task = Box<dyn Future<Output = ()>>;
 
 thread_local! {
     static CURRENT_EXEC: ExecutorCore = ExecutorCore::default();
@@ -19,16 +20,21 @@ struct ExecutorCore {
     next_id: Cell<usize>,
 }
 
-pub fn spawn<F>(future: F)
+/// Spawns a task on the executor of the current thread. The returned
+/// `JoinHandle` resolves to the output of `future` once the task completes.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
 where
-    F: Future<Output = String> + 'static,
+    F: Future + 'static,
+    F::Output: 'static,
 {
+    let (task, handle) = task::new_task(future);
     CURRENT_EXEC.with(|e| {
         let id = e.next_id.get();
-        e.tasks.borrow_mut().insert(id, Box::new(future));
+        e.tasks.borrow_mut().insert(id, Box::new(task));
         e.ready_queue.lock().map(|mut q| q.push(id)).unwrap();
         e.next_id.set(id + 1);
     });
+    handle
 }
 
 pub struct Executor;
@@ -0,0 +1,339 @@
+use std::{
+    any::Any,
+    cell::RefCell,
+    error::Error,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+};
+
+use crate::future::{Future, PollState};
+
+use super::Waker;
+
+/// The reason a task didn't produce an output.
+pub struct JoinError {
+    repr: Repr,
+}
+
+enum Repr {
+    Cancelled,
+    Panic(Box<dyn Any + Send>),
+}
+
+impl JoinError {
+    fn cancelled() -> Self {
+        Self {
+            repr: Repr::Cancelled,
+        }
+    }
+
+    fn panic(payload: Box<dyn Any + Send>) -> Self {
+        Self {
+            repr: Repr::Panic(payload),
+        }
+    }
+
+    /// The task was aborted through its `JoinHandle` before it completed.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self.repr, Repr::Cancelled)
+    }
+
+    /// The task panicked while being polled.
+    pub fn is_panic(&self) -> bool {
+        matches!(self.repr, Repr::Panic(_))
+    }
+
+    /// Returns the panic payload, so you can for example continue unwinding
+    /// with `std::panic::resume_unwind`.
+    ///
+    /// # Panics
+    /// If the task didn't panic.
+    pub fn into_panic(self) -> Box<dyn Any + Send> {
+        self.try_into_panic()
+            .expect("`JoinError` reason is not a panic")
+    }
+
+    pub fn try_into_panic(self) -> Result<Box<dyn Any + Send>, JoinError> {
+        match self.repr {
+            Repr::Panic(payload) => Ok(payload),
+            _ => Err(self),
+        }
+    }
+}
+
+/// Most panics carry either a `&str` or a `String`
+fn panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(|s| s.as_str()))
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.repr {
+            Repr::Cancelled => write!(f, "task was cancelled"),
+            Repr::Panic(p) => match panic_message(p.as_ref()) {
+                Some(msg) => write!(f, "task panicked with message {msg:?}"),
+                None => write!(f, "task panicked"),
+            },
+        }
+    }
+}
+
+impl fmt::Debug for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.repr {
+            Repr::Cancelled => write!(f, "JoinError::Cancelled"),
+            Repr::Panic(p) => match panic_message(p.as_ref()) {
+                Some(msg) => write!(f, "JoinError::Panic({msg:?})"),
+                None => write!(f, "JoinError::Panic(..)"),
+            },
+        }
+    }
+}
+
+impl Error for JoinError {}
+
+/// State shared between a spawned task and its `JoinHandle`. Both live on
+/// the thread of the executor the task was spawned on.
+struct JoinState<T> {
+    output: Option<Result<T, JoinError>>,
+    finished: bool,
+    aborted: bool,
+    // The task waiting on the `JoinHandle`
+    join_waker: Option<Waker>,
+    // The spawned task itself, so `abort` can get it polled one last time
+    task_waker: Option<Waker>,
+}
+
+type Shared<T> = Rc<RefCell<JoinState<T>>>;
+
+fn complete<T>(state: &Shared<T>, output: Result<T, JoinError>) {
+    let waker = {
+        let mut state = state.borrow_mut();
+        state.output = Some(output);
+        state.finished = true;
+        state.task_waker = None;
+        state.join_waker.take()
+    };
+
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+/// Wraps the spawned future so its output ends up in the `JoinHandle`
+/// instead of being thrown away. This is what the executor stores in its
+/// task map.
+pub(crate) struct Harness<F: Future> {
+    future: Option<F>,
+    state: Shared<F::Output>,
+}
+
+pub(crate) fn new_task<F: Future>(future: F) -> (Harness<F>, JoinHandle<F::Output>) {
+    let state = Rc::new(RefCell::new(JoinState {
+        output: None,
+        finished: false,
+        aborted: false,
+        join_waker: None,
+        task_waker: None,
+    }));
+
+    let harness = Harness {
+        future: Some(future),
+        state: state.clone(),
+    };
+
+    (harness, JoinHandle { state })
+}
+
+impl<F: Future> Future for Harness<F> {
+    type Output = ();
+
+    fn poll(&mut self, waker: &Waker) -> PollState<Self::Output> {
+        {
+            let mut state = self.state.borrow_mut();
+            if state.aborted {
+                drop(state);
+                // Drop the future before anyone is told the task is gone
+                self.future = None;
+                complete(&self.state, Err(JoinError::cancelled()));
+                return PollState::Ready(());
+            }
+            state.task_waker = Some(waker.clone());
+        }
+
+        let future = self
+            .future
+            .as_mut()
+            .expect("Harness polled after completion");
+
+        // The future can't be used after it panicked, so we treat it as
+        // completed
+        match panic::catch_unwind(AssertUnwindSafe(|| future.poll(waker))) {
+            Ok(PollState::Ready(output)) => {
+                self.future = None;
+                complete(&self.state, Ok(output));
+                PollState::Ready(())
+            }
+            Ok(PollState::NotReady) => PollState::NotReady,
+            Err(payload) => {
+                self.future = None;
+                complete(&self.state, Err(JoinError::panic(payload)));
+                PollState::Ready(())
+            }
+        }
+    }
+}
+
+/// Returned by `spawn`. Polling it gives you the output of the task, or a
+/// `JoinError` if the task panicked or was aborted.
+///
+/// Has to be polled by a task on the same executor as the task it belongs
+/// to, which is always the case for handles returned by `spawn`.
+pub struct JoinHandle<T> {
+    state: Shared<T>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Cancels the task. The future is dropped the next time the executor
+    /// gets to it, and the handle resolves to `JoinError::Cancelled`. Does
+    /// nothing if the task has already finished.
+    pub fn abort(&self) {
+        let waker = {
+            let mut state = self.state.borrow_mut();
+            if state.finished {
+                return;
+            }
+            state.aborted = true;
+            state.task_waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Returns `true` if the task has completed, been aborted or panicked.
+    pub fn is_finished(&self) -> bool {
+        self.state.borrow().finished
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(&mut self, waker: &Waker) -> PollState<Self::Output> {
+        let mut state = self.state.borrow_mut();
+        if let Some(output) = state.output.take() {
+            return PollState::Ready(output);
+        }
+
+        if state.finished {
+            panic!("JoinHandle polled after completion");
+        }
+
+        state.join_waker = Some(waker.clone());
+        PollState::NotReady
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::{self, Executor};
+
+    struct Value(Option<i32>);
+
+    impl Future for Value {
+        type Output = i32;
+
+        fn poll(&mut self, _waker: &Waker) -> PollState<i32> {
+            PollState::Ready(self.0.take().unwrap())
+        }
+    }
+
+    struct Forever;
+
+    impl Future for Forever {
+        type Output = ();
+
+        fn poll(&mut self, _waker: &Waker) -> PollState<()> {
+            PollState::NotReady
+        }
+    }
+
+    /// Waits for `handle`, aborting the task first if `abort` is set.
+    struct Join<T> {
+        handle: JoinHandle<T>,
+        abort: bool,
+        output: Rc<RefCell<Option<Result<T, JoinError>>>>,
+    }
+
+    impl<T> Future for Join<T> {
+        type Output = String;
+
+        fn poll(&mut self, waker: &Waker) -> PollState<String> {
+            if self.abort {
+                self.handle.abort();
+            }
+            match self.handle.poll(waker) {
+                PollState::Ready(res) => {
+                    *self.output.borrow_mut() = Some(res);
+                    PollState::Ready(String::new())
+                }
+                PollState::NotReady => PollState::NotReady,
+            }
+        }
+    }
+
+    fn join<T: 'static>(handle: JoinHandle<T>, abort: bool) -> Result<T, JoinError> {
+        let output = Rc::new(RefCell::new(None));
+        Executor::new().block_on(Join {
+            handle,
+            abort,
+            output: output.clone(),
+        });
+        let res = output.borrow_mut().take();
+        res.unwrap()
+    }
+
+    #[test]
+    fn join_handle_returns_output() {
+        let handle = runtime::spawn(Value(Some(42)));
+        assert!(!handle.is_finished());
+        assert_eq!(join(handle, false).unwrap(), 42);
+    }
+
+    #[test]
+    fn panicking_task_gives_join_error() {
+        let handle = runtime::spawn(Value(None));
+        let err = join(handle, false).unwrap_err();
+        assert!(err.is_panic());
+        assert_eq!(
+            err.to_string(),
+            "task panicked with message \"called `Option::unwrap()` on a `None` value\""
+        );
+        assert!(err.into_panic().is::<&str>());
+    }
+
+    #[test]
+    fn aborted_task_is_cancelled() {
+        let handle = runtime::spawn(Forever);
+        let err = join(handle, true).unwrap_err();
+        assert!(err.is_cancelled());
+        assert!(err.try_into_panic().is_err());
+    }
+
+    #[test]
+    fn finished_task_ignores_abort() {
+        let handle = runtime::spawn(Value(Some(1)));
+        // Runs the task of `handle` as well
+        join(runtime::spawn(Value(Some(2))), false).unwrap();
+        assert!(handle.is_finished());
+        handle.abort();
+        assert_eq!(join(handle, false).unwrap(), 1);
+    }
+}
@@ -16,46 +16,310 @@ pub enum PollState<T> {
 // =======================================================
 // Optional - we won't use this going forward
 // =======================================================
+
+/// Waits for every future in `futures` and returns their outputs in the same
+/// order.
 #[allow(dead_code)]
 pub fn join_all<F: Future>(futures: Vec<F>) -> JoinAll<F> {
-    let futures = futures.into_iter().map(|f| (false, Box::pin(f))).collect();
-    JoinAll {
-        futures,
-        finished_count: 0,
-    }
+    let futures = futures.into_iter().map(MaybeDone::new).collect();
+    JoinAll { futures }
 }
 
 pub struct JoinAll<F: Future> {
-    futures: Vec<(bool, Pin<Box<F>>)>,
-    finished_count: usize,
+    futures: Vec<MaybeDone<F>>,
 }
 
+// `MaybeDone` boxes the futures, so nothing in here is ever pinned
+impl<F: Future> Unpin for JoinAll<F> {}
+
 impl<F: Future> Future for JoinAll<F> {
-    type Output = String;
+    type Output = Vec<F::Output>;
     fn poll(mut self: Pin<&mut Self>, waker: &Waker) -> PollState<Self::Output> {
-        let Self {
-            futures,
-            finished_count,
-        } = &mut *self;
-        for (finished, fut) in futures.iter_mut() {
-            if *finished {
-                continue;
-            }
+        let mut all_done = true;
+        for fut in self.futures.iter_mut() {
+            all_done &= fut.poll_done(waker);
+        }
 
+        if all_done {
+            PollState::Ready(self.futures.iter_mut().map(|f| f.take_output()).collect())
+        } else {
+            PollState::NotReady
+        }
+    }
+}
+
+/// A future that's polled until it's done, and then holds on to its output
+/// until we take it. Used by `join_all` and the `join!` family of macros.
+pub enum MaybeDone<F: Future> {
+    Future(Pin<Box<F>>),
+    Done(F::Output),
+    Gone,
+}
+
+impl<F: Future> MaybeDone<F> {
+    pub fn new(future: F) -> Self {
+        MaybeDone::Future(Box::pin(future))
+    }
+
+    /// Polls the future if it isn't done yet. Returns `true` once it is.
+    pub fn poll_done(&mut self, waker: &Waker) -> bool {
+        if let MaybeDone::Future(fut) = self {
             match fut.as_mut().poll(waker) {
-                PollState::Ready(_) => {
-                    *finished = true;
-                    *finished_count += 1;
+                PollState::Ready(output) => *self = MaybeDone::Done(output),
+                PollState::NotReady => return false,
+            }
+        }
+        true
+    }
+
+    #[allow(dead_code)] // Only used by `try_join!`
+    pub fn output_mut(&mut self) -> Option<&mut F::Output> {
+        match self {
+            MaybeDone::Done(output) => Some(output),
+            _ => None,
+        }
+    }
+
+    /// # Panics
+    /// If the future isn't done, or the output has already been taken.
+    pub fn take_output(&mut self) -> F::Output {
+        match std::mem::replace(self, MaybeDone::Gone) {
+            MaybeDone::Done(output) => output,
+            _ => panic!("MaybeDone: output not available"),
+        }
+    }
+}
+
+/// Creates a future out of a closure that's called every time the future is
+/// polled. This is what the `join!` and `select!` macros expand to, since we
+/// can't write `async` blocks for our own `Future` trait.
+#[allow(dead_code)]
+pub fn poll_fn<T, F>(f: F) -> PollFn<F>
+where
+    F: FnMut(&Waker) -> PollState<T>,
+{
+    PollFn(f)
+}
+
+pub struct PollFn<F>(F);
+
+// We never hand out a pinned reference to the closure
+impl<F> Unpin for PollFn<F> {}
+
+impl<T, F> Future for PollFn<F>
+where
+    F: FnMut(&Waker) -> PollState<T>,
+{
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, waker: &Waker) -> PollState<Self::Output> {
+        (self.get_mut().0)(waker)
+    }
+}
+
+/// Polls every future concurrently and returns a tuple with their outputs
+/// once all of them are done: `join!(a, b, c)` gives you `(a, b, c)`.
+#[macro_export]
+macro_rules! join {
+    // Each future gets a list of `_` as long as its index, so we can pick it
+    // out of the tuple with `(_, _, fut, ..)`.
+    (@ { $( { $($skip:tt)* } $fut:expr, )* } { $($unused:tt)* }) => {{
+        let mut futures = ( $( $crate::future::MaybeDone::new($fut), )* );
+        $crate::future::poll_fn(move |waker| {
+            let mut all_done = true;
+            $(
+                let ( $($skip,)* fut, .. ) = &mut futures;
+                all_done &= fut.poll_done(waker);
+            )*
+
+            if !all_done {
+                return $crate::future::PollState::NotReady;
+            }
+
+            $crate::future::PollState::Ready(( $({
+                let ( $($skip,)* fut, .. ) = &mut futures;
+                fut.take_output()
+            }, )* ))
+        })
+    }};
+
+    (@ { $($parsed:tt)* } { $($skip:tt)* } $fut:expr, $($rest:tt)*) => {
+        $crate::join!(@ { $($parsed)* { $($skip)* } $fut, } { $($skip)* _ } $($rest)*)
+    };
+
+    ( $($fut:expr),+ $(,)? ) => {
+        $crate::join!(@ {} {} $($fut,)+)
+    };
+}
+
+/// Like `join!`, but for futures returning `Result`. Returns `Ok` with a
+/// tuple of the values if they all succeed, or the first `Err` as soon as
+/// one of them fails. The other futures aren't polled after that, and are
+/// dropped together with the future `try_join!` returns.
+#[macro_export]
+macro_rules! try_join {
+    (@ { $( { $($skip:tt)* } $fut:expr, )* } { $($unused:tt)* }) => {{
+        let mut futures = ( $( $crate::future::MaybeDone::new($fut), )* );
+        $crate::future::poll_fn(move |waker| {
+            let mut all_done = true;
+            $(
+                let ( $($skip,)* fut, .. ) = &mut futures;
+                all_done &= fut.poll_done(waker);
+                if let Some(Err(_)) = fut.output_mut() {
+                    let err = fut.take_output().err().unwrap();
+                    return $crate::future::PollState::Ready(Err(err));
                 }
+            )*
 
-                PollState::NotReady => continue,
+            if !all_done {
+                return $crate::future::PollState::NotReady;
             }
+
+            $crate::future::PollState::Ready(Ok(( $({
+                let ( $($skip,)* fut, .. ) = &mut futures;
+                fut.take_output().ok().unwrap()
+            }, )* )))
+        })
+    }};
+
+    (@ { $($parsed:tt)* } { $($skip:tt)* } $fut:expr, $($rest:tt)*) => {
+        $crate::try_join!(@ { $($parsed)* { $($skip)* } $fut, } { $($skip)* _ } $($rest)*)
+    };
+
+    ( $($fut:expr),+ $(,)? ) => {
+        $crate::try_join!(@ {} {} $($fut,)+)
+    };
+}
+
+/// Polls the futures concurrently and, as soon as one of them completes,
+/// binds its output to the pattern and evaluates that branch's expression.
+/// The other futures are dropped (cancelled) right away.
+///
+/// ```ignore
+/// let first = select! {
+///     a = Http::get("/600/slow") => a,
+///     b = Http::get("/100/fast") => b,
+/// };
+/// ```
+///
+/// Branches are polled in the order they're written, and the patterns must
+/// be irrefutable.
+///
+/// The handlers run inside the future `select!` returns, not in the function
+/// that uses it, so `return` or `?` in a handler doesn't return from that
+/// function. Have the handlers evaluate to a value and act on it after the
+/// `select!` instead.
+#[macro_export]
+macro_rules! select {
+    (@ { $( { $($skip:tt)* } $bind:pat = $fut:expr => $handler:expr, )* } { $($unused:tt)* }) => {{
+        let mut futures = Some(( $( Box::pin($fut), )* ));
+        $crate::future::poll_fn(move |waker| {
+            let Some(futs) = futures.as_mut() else {
+                panic!("select! polled after completion");
+            };
+
+            $(
+                let ( $($skip,)* fut, .. ) = futs;
+                if let $crate::future::PollState::Ready(output) =
+                    $crate::future::Future::poll(fut.as_mut(), waker)
+                {
+                    // Drop the branches that lost
+                    futures = None;
+                    let $bind = output;
+                    return $crate::future::PollState::Ready($handler);
+                }
+            )*
+
+            $crate::future::PollState::NotReady
+        })
+    }};
+
+    (@ { $($parsed:tt)* } { $($skip:tt)* } $bind:pat = $fut:expr => $handler:expr, $($rest:tt)*) => {
+        $crate::select!(@ { $($parsed)* { $($skip)* } $bind = $fut => $handler, } { $($skip)* _ } $($rest)*)
+    };
+
+    ( $($bind:pat = $fut:expr => $handler:expr),+ $(,)? ) => {
+        $crate::select!(@ {} {} $($bind = $fut => $handler,)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::runtime::Executor;
+
+    /// Not ready the first `polls` times it's polled, waking itself each
+    /// time, and then returns `output`.
+    struct Countdown<T> {
+        polls: usize,
+        output: Option<T>,
+    }
+
+    fn countdown<T>(polls: usize, output: T) -> Countdown<T> {
+        Countdown {
+            polls,
+            output: Some(output),
         }
+    }
 
-        if self.finished_count == self.futures.len() {
-            PollState::Ready(String::new())
-        } else {
+    impl<T> Unpin for Countdown<T> {}
+
+    impl<T> Future for Countdown<T> {
+        type Output = T;
+
+        fn poll(mut self: Pin<&mut Self>, waker: &Waker) -> PollState<T> {
+            if self.polls == 0 {
+                return PollState::Ready(self.output.take().unwrap());
+            }
+            self.polls -= 1;
+            waker.wake();
             PollState::NotReady
         }
     }
+
+    /// Runs `future` on the book executor and returns its output.
+    fn run<T: 'static>(future: impl Future<Output = T> + 'static) -> T {
+        let output = Rc::new(RefCell::new(None));
+        let mut future = Box::pin(future);
+        let out = output.clone();
+        Executor::new().block_on(poll_fn(move |waker| match future.as_mut().poll(waker) {
+            PollState::Ready(value) => {
+                *out.borrow_mut() = Some(value);
+                PollState::Ready(String::new())
+            }
+            PollState::NotReady => PollState::NotReady,
+        }));
+        let value = output.borrow_mut().take();
+        value.unwrap()
+    }
+
+    #[test]
+    fn join_all_keeps_the_order_of_the_futures() {
+        let futures = vec![countdown(3, 1), countdown(0, 2), countdown(1, 3)];
+        assert_eq!(run(join_all(futures)), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn join_returns_every_output() {
+        let joined = crate::join!(countdown(2, "a"), countdown(0, 1));
+        assert_eq!(run(joined), ("a", 1));
+    }
+
+    #[test]
+    fn try_join_returns_the_first_error() {
+        let ok = countdown(5, Ok::<_, &str>(1));
+        let err = countdown(1, Err::<u8, _>("failed"));
+        assert_eq!(run(crate::try_join!(ok, err)), Err("failed"));
+    }
+
+    #[test]
+    fn select_returns_the_first_to_complete() {
+        let first = crate::select! {
+            a = countdown(3, "slow") => a,
+            b = countdown(1, "fast") => b,
+        };
+        assert_eq!(run(first), "fast");
+    }
 }
@@ -1,4 +1,4 @@
-use crate::runtime::{self, reactor};
+use crate::runtime::{self, scheduled_io::Direction, Registration};
 use mio::Interest;
 use std::{
     future::Future,
@@ -24,20 +24,21 @@ impl Http {
     }
 }
 struct HttpGetFuture {
+    // Dropping it cleans up the reactor even if we never see `Ok(0)`.
+    // Declared before `stream` so that happens before the stream is closed.
+    registration: Option<Registration>,
     stream: Option<mio::net::TcpStream>,
     buffer: Vec<u8>,
     path: String,
-    id: usize,
 }
 
 impl HttpGetFuture {
     fn new(path: String) -> Self {
-        let id = reactor().next_id();
         Self {
+            registration: None,
             stream: None,
             buffer: vec![],
             path,
-            id,
         }
     }
 
@@ -59,34 +60,48 @@ impl Future for HttpGetFuture {
         // Avoid dns lookup this time
         //let this = self.get_mut();
 
-        let id = self.id;
         if self.stream.is_none() {
             println!("FIRST POLL - START OPERATION");
             self.write_request();
             // CHANGED
-            let stream = (&mut self).stream.as_mut().unwrap();
-            runtime::reactor().register(stream, Interest::READABLE, id);
-            runtime::reactor().set_waker(cx, self.id);
+            let stream = self.stream.as_mut().unwrap();
+            let registration = runtime::reactor()
+                .register(stream, Interest::READABLE)
+                .unwrap();
+            self.registration = Some(registration);
             // ============
         }
 
+        let this = &mut *self;
         let mut buff = vec![0u8; 147];
         loop {
-            match self.stream.as_mut().unwrap().read(&mut buff) {
+            // A fast server would otherwise keep us here for the whole response
+            let Poll::Ready(coop) = runtime::coop::poll_proceed(cx) else {
+                break Poll::Pending;
+            };
+            // Stores the waker and checks for an event in one go, so we can't
+            // miss one that comes in between
+            let scheduled_io = this.registration.as_ref().unwrap().scheduled_io();
+            let Poll::Ready(event) = scheduled_io.poll_ready(Direction::Read, cx) else {
+                break Poll::Pending;
+            };
+            match this.stream.as_mut().unwrap().read(&mut buff) {
                 Ok(0) => {
-                    let s = String::from_utf8_lossy(&self.buffer).to_string();
-                    runtime::reactor().deregister(self.stream.as_mut().unwrap(), id);
+                    coop.made_progress();
+                    let s = String::from_utf8_lossy(&this.buffer).to_string();
+                    let registration = this.registration.take().unwrap();
+                    registration
+                        .deregister(this.stream.as_mut().unwrap())
+                        .unwrap();
                     break Poll::Ready(s.to_string());
                 }
                 Ok(n) => {
-                    self.buffer.extend(&buff[0..n]);
+                    coop.made_progress();
+                    this.buffer.extend(&buff[0..n]);
                     continue;
                 }
-                Err(e) if e.kind() == ErrorKind::WouldBlock => {
-                    // always store the last given Waker
-                    runtime::reactor().set_waker(cx, self.id);
-                    break Poll::Pending;
-                }
+                // Wait for the next event
+                Err(e) if e.kind() == ErrorKind::WouldBlock => scheduled_io.clear_readiness(event),
 
                 Err(e) => panic!("{e:?}"),
             }
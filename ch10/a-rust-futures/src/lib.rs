@@ -0,0 +1,3 @@
+pub mod future;
+pub mod http;
+pub mod runtime;
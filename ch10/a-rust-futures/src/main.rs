@@ -1,11 +1,8 @@
-mod http;
-mod runtime;
-use crate::http::Http;
-
+use a_rust_futures::{http::Http, runtime};
 
 fn main() {
-    let mut executor = runtime::init();
-    executor.block_on(async_main());
+    let mut runtime = runtime::init();
+    runtime.block_on(async_main());
 }
 
 async fn async_main() {
@@ -15,4 +12,3 @@ async fn async_main() {
     let txt = Http::get("/400/HelloAsyncAwait").await;
     println!("{txt}");
 }
-
@@ -1,10 +1,158 @@
-pub use executor::{spawn, Executor};
-pub use reactor::reactor;
+pub use blocking::spawn_blocking;
+pub use builder::Builder;
+pub use context::{reactor, EnterGuard, Handle};
+pub use coop::{yield_now, YieldNow};
+pub use executor::{spawn, spawn_with_priority, Executor, SpawnedTasks};
+pub use metrics::{metrics, RuntimeMetrics, WorkerMetrics};
+pub use multi_thread::{MultiThreadExecutor, MultiThreadHandle};
+pub use reactor::{Reactor, ReactorStats, Registration};
+pub use scheduler::Scheduling;
+pub use task::{JoinError, JoinHandle, PanicHook};
+pub use task_local::{AccessError, LocalKey, TaskLocalFuture};
+pub use time::{interval, sleep, sleep_until, timeout, Elapsed, Interval, Sleep, Timeout};
 
+mod blocking;
+mod builder;
+#[cfg(feature = "chrome-trace")]
+pub mod chrome_trace;
+mod context;
+pub(crate) mod coop;
 mod executor;
+mod metrics;
+mod multi_thread;
+pub mod net;
 mod reactor;
+pub(crate) mod scheduled_io;
+mod scheduler;
+pub mod sync;
+mod task;
+mod task_local;
+mod time;
+mod wake_queue;
 
-pub fn init() -> Executor {
-    reactor::start();
-    Executor::new()
+use std::{future::Future, thread, time::Duration};
+
+/// Runs futures on top of a reactor that it owns. You can have as many of
+/// these as you want, on the same or different threads.
+///
+/// A current-thread runtime (the default) polls every task on the thread
+/// that calls `block_on`. A multi-thread runtime, see
+/// `Builder::new_multi_thread`, polls the future passed to `block_on` on the
+/// calling thread and spawned tasks on its worker threads.
+pub struct Runtime {
+    flavor: Flavor,
+    handle: Handle,
+    // `None` if the runtime was built without IO and time
+    reactor_thread: Option<thread::JoinHandle<()>>,
+    is_shutdown: bool,
+}
+
+enum Flavor {
+    CurrentThread(Executor),
+    MultiThread(MultiThreadExecutor),
+}
+
+impl Runtime {
+    /// A current-thread runtime with IO and time enabled. The executor runs
+    /// on the thread that calls `block_on`, which must be the thread the
+    /// runtime was created on.
+    ///
+    /// Use `Builder` to configure the runtime.
+    pub fn new() -> Self {
+        Builder::new().enable_all().build()
+    }
+
+    /// Runs `future` with this runtime as the current one, so `reactor()` and
+    /// `Handle::current()` resolve to it.
+    ///
+    /// On a multi-thread runtime, `runtime::spawn` isn't available from
+    /// `future`, use `MultiThreadHandle::current().spawn` instead.
+    pub fn block_on<F: Future>(&mut self, future: F) -> F::Output {
+        let _guard = self.handle.enter();
+        match &mut self.flavor {
+            Flavor::CurrentThread(executor) => executor.block_on(future),
+            Flavor::MultiThread(executor) => {
+                let _worker = executor.enter();
+                multi_thread::park_on(future)
+            }
+        }
+    }
+
+    /// Spawns a task. On a current-thread runtime it will run the next time
+    /// `block_on` is called, on a multi-thread runtime it starts right away.
+    #[track_caller]
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match &self.flavor {
+            Flavor::CurrentThread(executor) => executor.spawn(future),
+            Flavor::MultiThread(executor) => executor.spawn(future),
+        }
+    }
+
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// A snapshot of what the runtime has been up to, see `RuntimeMetrics`.
+    pub fn metrics(&self) -> RuntimeMetrics {
+        self.handle.metrics()
+    }
+
+    /// The executor of a current-thread runtime. `None` for a multi-thread
+    /// runtime.
+    pub fn executor_mut(&mut self) -> Option<&mut Executor> {
+        match &mut self.flavor {
+            Flavor::CurrentThread(executor) => Some(executor),
+            Flavor::MultiThread(_) => None,
+        }
+    }
+
+    /// Gives the remaining tasks `timeout` to finish, drops the ones that
+    /// didn't, and then stops the reactor and waits for its thread to exit.
+    /// Blocking jobs that haven't started are dropped, the ones that are
+    /// running are left to finish on their own.
+    pub fn shutdown(mut self, timeout: Duration) {
+        self.shutdown_inner(timeout);
+    }
+
+    fn shutdown_inner(&mut self, timeout: Duration) {
+        if self.is_shutdown {
+            return;
+        }
+        self.is_shutdown = true;
+
+        {
+            let _guard = self.handle.enter();
+            match &mut self.flavor {
+                Flavor::CurrentThread(executor) => executor.shutdown(timeout),
+                Flavor::MultiThread(executor) => executor.shutdown(timeout),
+            }
+        }
+        self.handle.blocking_pool().shutdown();
+        if let Some(reactor) = self.handle.reactor() {
+            reactor.stop();
+        }
+        if let Some(reactor_thread) = self.reactor_thread.take() {
+            reactor_thread.join().unwrap();
+        }
+    }
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        self.shutdown_inner(Duration::ZERO);
+    }
+}
+
+pub fn init() -> Runtime {
+    Runtime::new()
 }
@@ -0,0 +1,181 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use super::{
+    builder::ThreadConfig,
+    context::Handle,
+    task::{self, Harness, JoinHandle},
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Runs `f` on a thread in the blocking pool, so it doesn't stall the
+/// executor, and returns a handle you can await to get the result.
+///
+/// # Panics
+/// If called outside of a runtime context.
+pub fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    Handle::current().blocking_pool().spawn(f)
+}
+
+/// Lets us reuse `Harness` (and with it `JoinHandle`, aborting and panic
+/// handling) for closures: the first poll runs the closure to completion.
+struct BlockingTask<F>(Option<F>);
+
+impl<F, R> Future for BlockingTask<F>
+where
+    F: FnOnce() -> R + Unpin,
+{
+    type Output = R;
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Self::Output> {
+        let f = self.0.take().expect("BlockingTask polled after completion");
+        Poll::Ready(f())
+    }
+}
+
+struct State {
+    jobs: VecDeque<Job>,
+    num_threads: usize,
+    idle_threads: usize,
+    // Wakeups handed out to idle threads that haven't been picked up yet.
+    // Lets a thread tell a real notification from a spurious one.
+    num_notify: usize,
+    shutdown: bool,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    condvar: Condvar,
+    max_threads: usize,
+    keep_alive: Duration,
+    threads: ThreadConfig,
+}
+
+/// A pool of threads for running blocking code. Threads are started when a
+/// job comes in and there is no idle thread, up to `max_threads`. After
+/// that, jobs wait in a queue. Threads that have been idle for `keep_alive`
+/// exit.
+#[derive(Clone)]
+pub(crate) struct BlockingPool {
+    inner: Arc<Inner>,
+}
+
+impl BlockingPool {
+    pub(crate) fn new(max_threads: usize, keep_alive: Duration, threads: ThreadConfig) -> Self {
+        assert!(max_threads > 0, "need at least one blocking thread");
+        Self {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State {
+                    jobs: VecDeque::new(),
+                    num_threads: 0,
+                    idle_threads: 0,
+                    num_notify: 0,
+                    shutdown: false,
+                }),
+                condvar: Condvar::new(),
+                max_threads,
+                keep_alive,
+                threads,
+            }),
+        }
+    }
+
+    pub(crate) fn spawn<F, R>(&self, f: F) -> JoinHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        // The closure itself doesn't have to be `Unpin`, the box is
+        let (mut harness, handle) = task::new_task(BlockingTask(Some(Box::new(f))), None);
+        self.push(Box::new(move || run(&mut harness)));
+        handle
+    }
+
+    fn push(&self, job: Job) {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.shutdown {
+            // Dropping the job drops the harness, which cancels the task
+            return;
+        }
+
+        state.jobs.push_back(job);
+        if state.idle_threads > 0 {
+            state.idle_threads -= 1;
+            state.num_notify += 1;
+            self.inner.condvar.notify_one();
+        } else if state.num_threads < self.inner.max_threads {
+            state.num_threads += 1;
+            let inner = self.inner.clone();
+            let threads = &self.inner.threads;
+            threads.spawn(threads.name_or("blocking", ""), move || worker(inner));
+        }
+    }
+
+    /// Drops the jobs that haven't started yet and lets every thread exit
+    /// once it's done with the job it's running.
+    pub(crate) fn shutdown(&self) {
+        let jobs = {
+            let mut state = self.inner.state.lock().unwrap();
+            state.shutdown = true;
+            std::mem::take(&mut state.jobs)
+        };
+        self.inner.condvar.notify_all();
+        drop(jobs);
+    }
+}
+
+fn run<F: Future>(harness: &mut Harness<F>) {
+    // Nobody needs to be woken, `Harness` tells the `JoinHandle` when it's done
+    let mut cx = Context::from_waker(Waker::noop());
+    let _ = Pin::new(harness).poll(&mut cx);
+}
+
+fn worker(inner: Arc<Inner>) {
+    let mut state = inner.state.lock().unwrap();
+    loop {
+        if let Some(job) = state.jobs.pop_front() {
+            drop(state);
+            job();
+            state = inner.state.lock().unwrap();
+            continue;
+        }
+
+        if state.shutdown {
+            break;
+        }
+
+        state.idle_threads += 1;
+        loop {
+            let (guard, res) = inner.condvar.wait_timeout(state, inner.keep_alive).unwrap();
+            state = guard;
+
+            if state.num_notify > 0 {
+                state.num_notify -= 1;
+                break;
+            }
+
+            if state.shutdown || res.timed_out() {
+                state.idle_threads -= 1;
+                if res.timed_out() && !state.shutdown {
+                    state.num_threads -= 1;
+                    return;
+                }
+                break;
+            }
+            // Spurious wakeup, go back to sleep
+        }
+    }
+
+    state.num_threads -= 1;
+}
@@ -0,0 +1,318 @@
+use std::{any::Any, sync::Arc, thread, time::Duration};
+
+use super::{
+    blocking::BlockingPool, metrics::Metrics, reactor, Executor, Flavor, Handle,
+    MultiThreadExecutor, PanicHook, Runtime, Scheduling,
+};
+
+/// Called on a thread the runtime started, right after it starts or right
+/// before it exits.
+pub(crate) type ThreadHook = Arc<dyn Fn() + Send + Sync>;
+
+/// Configures and creates a `Runtime`.
+///
+/// ```no_run
+/// use a_rust_futures::runtime::{Builder, Scheduling};
+///
+/// let mut runtime = Builder::new()
+///     .scheduling(Scheduling::LifoSlot)
+///     .on_task_panic(|_| eprintln!("a task panicked"))
+///     .enable_all()
+///     .build();
+/// runtime.block_on(async {});
+///
+/// let mut runtime = Builder::new_multi_thread()
+///     .worker_threads(4)
+///     .thread_name("worker")
+///     .thread_stack_size(4 * 1024 * 1024)
+///     .enable_all()
+///     .build();
+/// runtime.block_on(async {});
+/// ```
+pub struct Builder {
+    // `None` is the current-thread runtime
+    worker_threads: Option<usize>,
+    scheduling: Scheduling,
+    panic_hook: Option<PanicHook>,
+    max_blocking_threads: usize,
+    thread_keep_alive: Duration,
+    threads: ThreadConfig,
+    event_capacity: usize,
+    reactor_thread: bool,
+    detect_lost_wakeups: bool,
+    enable_io: bool,
+    enable_time: bool,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            worker_threads: None,
+            scheduling: Scheduling::default(),
+            panic_hook: None,
+            max_blocking_threads: 64,
+            thread_keep_alive: Duration::from_secs(10),
+            threads: ThreadConfig::default(),
+            event_capacity: 100,
+            reactor_thread: true,
+            detect_lost_wakeups: false,
+            enable_io: false,
+            enable_time: false,
+        }
+    }
+}
+
+impl Builder {
+    /// Same as `new_current_thread`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A runtime that polls every task on the thread that calls `block_on`.
+    pub fn new_current_thread() -> Self {
+        Self::default()
+    }
+
+    /// A runtime that polls spawned tasks on a pool of worker threads, one
+    /// per CPU core unless `worker_threads` says otherwise.
+    pub fn new_multi_thread() -> Self {
+        let worker_threads = thread::available_parallelism().map_or(1, |n| n.get());
+        Self {
+            worker_threads: Some(worker_threads),
+            ..Self::default()
+        }
+    }
+
+    /// How many worker threads a multi-thread runtime starts. Ignored by the
+    /// current-thread runtime.
+    pub fn worker_threads(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0, "need at least one worker thread");
+        if let Some(worker_threads) = &mut self.worker_threads {
+            *worker_threads = n;
+        }
+        self
+    }
+
+    /// The order woken tasks are polled in. Defaults to `Scheduling::Fifo`.
+    /// Only used by the current-thread runtime.
+    pub fn scheduling(&mut self, scheduling: Scheduling) -> &mut Self {
+        self.scheduling = scheduling;
+        self
+    }
+
+    /// Called with the panic payload whenever a spawned task panics. The
+    /// panicking task is removed either way and the others keep running.
+    pub fn on_task_panic<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(&(dyn Any + Send)) + Send + Sync + 'static,
+    {
+        self.panic_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// The most threads `spawn_blocking` will start. When they're all busy,
+    /// new jobs wait in a queue. Defaults to 64.
+    pub fn max_blocking_threads(&mut self, max: usize) -> &mut Self {
+        assert!(max > 0, "need at least one blocking thread");
+        self.max_blocking_threads = max;
+        self
+    }
+
+    /// How long an idle blocking thread waits for a new job before it exits.
+    /// Defaults to 10 seconds.
+    pub fn thread_keep_alive(&mut self, keep_alive: Duration) -> &mut Self {
+        self.thread_keep_alive = keep_alive;
+        self
+    }
+
+    /// Names the threads the runtime starts. Worker threads get their index
+    /// appended (`name-0`, `name-1`...) and the reactor thread gets
+    /// `-reactor`. Without a name, workers are called `exec-0`, `exec-1`...,
+    /// blocking threads `blocking` and the reactor thread `reactor`.
+    pub fn thread_name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.threads.name = Some(name.into());
+        self
+    }
+
+    /// The stack size in bytes of the worker and blocking threads. Defaults
+    /// to whatever `std::thread` uses.
+    pub fn thread_stack_size(&mut self, size: usize) -> &mut Self {
+        self.threads.stack_size = Some(size);
+        self
+    }
+
+    /// Called on every worker and blocking thread before it starts running
+    /// tasks.
+    pub fn on_thread_start<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.threads.on_start = Some(Arc::new(f));
+        self
+    }
+
+    /// Called on every worker and blocking thread right before it exits.
+    pub fn on_thread_stop<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.threads.on_stop = Some(Arc::new(f));
+        self
+    }
+
+    /// The most events the reactor handles per call to `mio::Poll::poll`.
+    /// Defaults to 100.
+    pub fn event_capacity(&mut self, capacity: usize) -> &mut Self {
+        assert!(capacity > 0, "`capacity` must be non-zero");
+        self.event_capacity = capacity;
+        self
+    }
+
+    /// Runs the reactor on the thread that calls `block_on` instead of
+    /// starting a thread for it. Whenever there's no task to poll, the
+    /// executor waits for IO events and timers itself, and while there are
+    /// tasks it checks for events every now and then without blocking.
+    ///
+    /// That saves a thread and a cross-thread wakeup per IO event, but
+    /// nothing happens while `block_on` isn't running. Only used by the
+    /// current-thread runtime, a multi-thread runtime always starts a reactor
+    /// thread.
+    pub fn reactor_on_executor_thread(&mut self) -> &mut Self {
+        self.reactor_thread = false;
+        self
+    }
+
+    /// Panic with the stuck tasks instead of hanging when `block_on` is
+    /// about to sleep but nothing can ever wake it, see
+    /// `Executor::detect_lost_wakeups`. Off by default. Only used by the
+    /// current-thread runtime.
+    pub fn detect_lost_wakeups(&mut self, enabled: bool) -> &mut Self {
+        self.detect_lost_wakeups = enabled;
+        self
+    }
+
+    /// Lets futures register sockets with the reactor.
+    pub fn enable_io(&mut self) -> &mut Self {
+        self.enable_io = true;
+        self
+    }
+
+    /// Lets futures use `sleep`, `interval` and `timeout`.
+    pub fn enable_time(&mut self) -> &mut Self {
+        self.enable_time = true;
+        self
+    }
+
+    /// Enables both IO and time.
+    pub fn enable_all(&mut self) -> &mut Self {
+        self.enable_io().enable_time()
+    }
+
+    pub fn build(&mut self) -> Runtime {
+        let current_thread = self.worker_threads.is_none();
+        let metrics = Arc::new(Metrics::new(self.worker_threads.unwrap_or(1)));
+
+        // Without IO or time there's nothing for a reactor to do, so we
+        // don't start one
+        let (reactor, reactor_thread, driver) = if !self.enable_io && !self.enable_time {
+            (None, None, None)
+        } else if current_thread && !self.reactor_thread {
+            let driver = reactor::new(
+                self.event_capacity,
+                self.enable_io,
+                self.enable_time,
+                metrics.clone(),
+            );
+            (Some(driver.reactor().clone()), None, Some(driver))
+        } else {
+            let (reactor, thread) = reactor::start(
+                self.event_capacity,
+                self.enable_io,
+                self.enable_time,
+                self.threads.name_or("reactor", "-reactor"),
+                metrics.clone(),
+            );
+            (Some(reactor), Some(thread), None)
+        };
+
+        let blocking_pool = BlockingPool::new(
+            self.max_blocking_threads,
+            self.thread_keep_alive,
+            self.threads.clone(),
+        );
+        let handle = Handle::new(reactor, blocking_pool, metrics.clone());
+
+        let flavor = match self.worker_threads {
+            None => {
+                let mut executor = match driver {
+                    Some(driver) => Executor::with_driver(self.scheduling, driver),
+                    None => Executor::new(self.scheduling),
+                };
+                executor.set_metrics(metrics);
+                executor.detect_lost_wakeups(self.detect_lost_wakeups);
+                if let Some(hook) = &self.panic_hook {
+                    executor.on_task_panic(hook.clone());
+                }
+                Flavor::CurrentThread(executor)
+            }
+            Some(n) => Flavor::MultiThread(MultiThreadExecutor::with_config(
+                n,
+                &self.threads,
+                self.panic_hook.clone(),
+                Some(handle.clone()),
+                metrics,
+            )),
+        };
+
+        Runtime {
+            flavor,
+            handle,
+            reactor_thread,
+            is_shutdown: false,
+        }
+    }
+}
+
+/// How the runtime starts its worker and blocking threads.
+#[derive(Clone, Default)]
+pub(crate) struct ThreadConfig {
+    name: Option<String>,
+    stack_size: Option<usize>,
+    on_start: Option<ThreadHook>,
+    on_stop: Option<ThreadHook>,
+}
+
+impl ThreadConfig {
+    /// The configured name followed by `suffix`, or `default` if no name was
+    /// configured.
+    pub(crate) fn name_or(&self, default: &str, suffix: &str) -> String {
+        match &self.name {
+            Some(name) => format!("{name}{suffix}"),
+            None => default.to_string(),
+        }
+    }
+
+    pub(crate) fn spawn<F>(&self, name: String, f: F) -> thread::JoinHandle<()>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut builder = thread::Builder::new().name(name);
+        if let Some(size) = self.stack_size {
+            builder = builder.stack_size(size);
+        }
+
+        let on_start = self.on_start.clone();
+        let on_stop = self.on_stop.clone();
+        builder
+            .spawn(move || {
+                if let Some(on_start) = on_start {
+                    on_start();
+                }
+                f();
+                if let Some(on_stop) = on_stop {
+                    on_stop();
+                }
+            })
+            .unwrap()
+    }
+}
@@ -0,0 +1,281 @@
+//! A `tracing` layer that writes what the runtime does in the Chrome trace
+//! format, so you can open it in `chrome://tracing` or
+//! <https://ui.perfetto.dev>.
+//!
+//! Every poll of a task shows up as a slice called `task id=N` on the
+//! thread it ran on (`exec-0`, `exec-1`...), and wakeups, spawns and IO
+//! events show up as instants.
+//!
+//! ```no_run
+//! use a_rust_futures::runtime::{chrome_trace::ChromeLayer, Runtime};
+//! use tracing_subscriber::layer::SubscriberExt;
+//!
+//! let (layer, _guard) = ChromeLayer::file("trace.json").unwrap();
+//! let subscriber = tracing_subscriber::registry().with(layer);
+//! tracing::subscriber::set_global_default(subscriber).unwrap();
+//!
+//! let mut rt = Runtime::new();
+//! rt.block_on(async { /* ... */ });
+//! // The trace is complete once `_guard` is dropped
+//! ```
+
+use std::{
+    cell::Cell,
+    collections::HashSet,
+    fmt::{self, Write as _},
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Instant,
+};
+
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+static NEXT_TID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    // Chrome wants small integer thread ids, `ThreadId` can't give us those
+    static TID: Cell<u64> = const { Cell::new(0) };
+}
+
+fn current_tid() -> u64 {
+    TID.with(|tid| {
+        if tid.get() == 0 {
+            tid.set(NEXT_TID.fetch_add(1, Ordering::Relaxed));
+        }
+        tid.get()
+    })
+}
+
+/// Writes spans and events as Chrome trace events. Created together with a
+/// `FlushGuard` that finishes the trace when dropped.
+pub struct ChromeLayer {
+    out: Arc<Mutex<Output>>,
+    start: Instant,
+}
+
+/// Finishes the trace and flushes it when dropped. Anything recorded after
+/// that is thrown away.
+#[must_use]
+pub struct FlushGuard {
+    out: Arc<Mutex<Output>>,
+}
+
+struct Output {
+    // `None` once the trace is finished
+    writer: Option<Box<dyn Write + Send>>,
+    first: bool,
+    // Threads we've written the name of
+    named: HashSet<u64>,
+}
+
+impl ChromeLayer {
+    pub fn new(writer: impl Write + Send + 'static) -> (Self, FlushGuard) {
+        let out = Arc::new(Mutex::new(Output {
+            writer: Some(Box::new(writer)),
+            first: true,
+            named: HashSet::new(),
+        }));
+        let layer = Self {
+            out: out.clone(),
+            start: Instant::now(),
+        };
+        (layer, FlushGuard { out })
+    }
+
+    /// Writes the trace to a new file at `path`.
+    pub fn file(path: impl AsRef<Path>) -> io::Result<(Self, FlushGuard)> {
+        let file = File::create(path)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+
+    fn write(&self, name: &str, cat: &str, ph: &str, args: &str) {
+        let ts = self.start.elapsed().as_secs_f64() * 1_000_000.0;
+        let tid = current_tid();
+        let mut out = self.out.lock().unwrap();
+
+        if out.named.insert(tid) {
+            let thread = thread::current();
+            let thread_name = thread.name().unwrap_or("unnamed");
+            out.record(&format!(
+                r#"{{"name":"thread_name","ph":"M","pid":1,"tid":{tid},"args":{{"name":{}}}}}"#,
+                json_string(thread_name)
+            ));
+        }
+
+        let scope = if ph == "i" { r#","s":"t""# } else { "" };
+        out.record(&format!(
+            r#"{{"name":{},"cat":{},"ph":"{ph}","ts":{ts:.3},"pid":1,"tid":{tid}{scope},"args":{args}}}"#,
+            json_string(name),
+            json_string(cat),
+        ));
+    }
+}
+
+impl Output {
+    fn record(&mut self, json: &str) {
+        let Some(writer) = &mut self.writer else {
+            return;
+        };
+
+        let res = if self.first {
+            self.first = false;
+            write!(writer, "[\n{json}")
+        } else {
+            write!(writer, ",\n{json}")
+        };
+        // Tracing has nowhere to report errors to, so give up on the trace
+        if res.is_err() {
+            self.writer = None;
+        }
+    }
+}
+
+impl Drop for FlushGuard {
+    fn drop(&mut self) {
+        let mut out = self.out.lock().unwrap();
+        let first = out.first;
+        if let Some(mut writer) = out.writer.take() {
+            let end = if first { "[]\n" } else { "\n]\n" };
+            let _ = writer.write_all(end.as_bytes());
+            let _ = writer.flush();
+        }
+    }
+}
+
+/// The name and arguments of a span, worked out once when it's created.
+struct SpanInfo {
+    name: String,
+    args: String,
+}
+
+impl<S> Layer<S> for ChromeLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+
+        // `task id=3` reads better in the timeline than just `task`
+        let mut name = attrs.metadata().name().to_string();
+        for (field, value) in &fields.pairs {
+            let _ = write!(name, " {field}={}", value.trim_matches('"'));
+        }
+
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanInfo {
+                name,
+                args: fields.args(),
+            });
+        }
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(info) = span.extensions().get::<SpanInfo>() {
+                self.write(&info.name, span.metadata().target(), "B", &info.args);
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(info) = span.extensions().get::<SpanInfo>() {
+                self.write(&info.name, span.metadata().target(), "E", "{}");
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+
+        // Say which task an event like "completed" is about
+        if let Some(span) = ctx.event_span(event) {
+            if let Some(info) = span.extensions().get::<SpanInfo>() {
+                fields.pairs.push(("span", json_string(&info.name)));
+            }
+        }
+
+        let name = fields
+            .message
+            .take()
+            .unwrap_or_else(|| event.metadata().name().to_string());
+        self.write(&name, event.metadata().target(), "i", &fields.args());
+    }
+}
+
+/// The fields of a span or event, with the values already turned into JSON.
+#[derive(Default)]
+struct Fields {
+    message: Option<String>,
+    pairs: Vec<(&'static str, String)>,
+}
+
+impl Fields {
+    fn args(&self) -> String {
+        let pairs: Vec<_> = self
+            .pairs
+            .iter()
+            .map(|(field, value)| format!("{}:{value}", json_string(field)))
+            .collect();
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+impl Visit for Fields {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.pairs.push((field.name(), value.to_string()));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.pairs.push((field.name(), value.to_string()));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.pairs.push((field.name(), value.to_string()));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.pairs.push((field.name(), json_string(value)));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let value = format!("{value:?}");
+        if field.name() == "message" {
+            self.message = Some(value);
+        } else {
+            self.pairs.push((field.name(), json_string(&value)));
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", u32::from(c));
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
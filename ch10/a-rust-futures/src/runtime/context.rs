@@ -0,0 +1,92 @@
+use std::{cell::RefCell, sync::Arc};
+
+use super::{
+    blocking::BlockingPool,
+    metrics::{Metrics, RuntimeMetrics},
+    reactor::Reactor,
+};
+
+thread_local! {
+    // Set while a runtime is running on this thread. This is how leaf
+    // futures like `HttpGetFuture` find the reactor to register with.
+    static CURRENT: RefCell<Option<Handle>> = const { RefCell::new(None) };
+}
+
+/// Returns the reactor of the runtime we're running on.
+///
+/// # Panics
+/// If called outside of a runtime context, or if the runtime was built with
+/// neither IO nor time enabled.
+pub fn reactor() -> Arc<Reactor> {
+    Handle::current()
+        .reactor
+        .expect("IO and time are disabled on this runtime, see `Builder::enable_all`")
+}
+
+/// A handle to a `Runtime`. It can be cloned and sent to other threads,
+/// where `enter` makes it the current runtime.
+#[derive(Clone)]
+pub struct Handle {
+    // `None` if neither IO nor time is enabled
+    reactor: Option<Arc<Reactor>>,
+    blocking_pool: BlockingPool,
+    metrics: Arc<Metrics>,
+}
+
+impl Handle {
+    pub(crate) fn new(
+        reactor: Option<Arc<Reactor>>,
+        blocking_pool: BlockingPool,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        Self {
+            reactor,
+            blocking_pool,
+            metrics,
+        }
+    }
+
+    /// The handle of the runtime we're currently running on.
+    ///
+    /// # Panics
+    /// If called outside of a runtime context.
+    pub fn current() -> Self {
+        Self::try_current().expect("called outside a runtime context")
+    }
+
+    pub fn try_current() -> Option<Self> {
+        CURRENT.with(|c| c.borrow().clone())
+    }
+
+    /// Makes this the current runtime on this thread until the returned
+    /// guard is dropped.
+    pub fn enter(&self) -> EnterGuard {
+        let prev = CURRENT.with(|c| c.borrow_mut().replace(self.clone()));
+        EnterGuard { prev }
+    }
+
+    /// A snapshot of what the runtime has been up to, see `RuntimeMetrics`.
+    pub fn metrics(&self) -> RuntimeMetrics {
+        self.metrics.snapshot()
+    }
+
+    pub(crate) fn reactor(&self) -> Option<&Arc<Reactor>> {
+        self.reactor.as_ref()
+    }
+
+    pub(crate) fn blocking_pool(&self) -> &BlockingPool {
+        &self.blocking_pool
+    }
+}
+
+/// Returned by `Handle::enter`. Puts back the previous runtime context (if
+/// any) when dropped.
+pub struct EnterGuard {
+    prev: Option<Handle>,
+}
+
+impl Drop for EnterGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|c| *c.borrow_mut() = self.prev.take());
+    }
+}
@@ -0,0 +1,143 @@
+//! Cooperative scheduling. A task that keeps getting `Ready` from the
+//! sockets it's reading never returns `Pending`, and the other tasks on its
+//! thread never get to run. So every task gets a budget each time it's
+//! polled, IO operations spend it, and once it's gone they return `Pending`
+//! and wake the task right away, putting it at the back of the queue. An
+//! operation that has to wait gets its unit back, since a task that returns
+//! `Pending` isn't hogging the thread.
+
+use std::{
+    cell::Cell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// How many IO operations a task gets per poll.
+const BUDGET: u8 = 128;
+
+thread_local! {
+    // `None` outside of a task poll, where nobody is waiting for the thread
+    static CURRENT: Cell<Option<u8>> = const { Cell::new(None) };
+}
+
+/// Runs `f`, which polls a task, with a fresh budget.
+pub(crate) fn budget<R>(f: impl FnOnce() -> R) -> R {
+    struct ResetGuard(Option<u8>);
+
+    impl Drop for ResetGuard {
+        fn drop(&mut self) {
+            CURRENT.with(|budget| budget.set(self.0));
+        }
+    }
+
+    let _reset = ResetGuard(CURRENT.with(|budget| budget.replace(Some(BUDGET))));
+    f()
+}
+
+/// Spends one unit of the budget of the current task. If there's nothing
+/// left the task is woken and `Pending` returned, so the caller should
+/// return `Pending` too without doing any work.
+///
+/// The unit is given back when the returned guard is dropped, unless
+/// `made_progress` was called on it first.
+pub(crate) fn poll_proceed(cx: &mut Context) -> Poll<RestoreOnPending> {
+    CURRENT.with(|budget| match budget.get() {
+        Some(0) => {
+            tracing::trace!("budget used up, yielding");
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        Some(n) => {
+            budget.set(Some(n - 1));
+            Poll::Ready(RestoreOnPending(Cell::new(true)))
+        }
+        None => Poll::Ready(RestoreOnPending(Cell::new(false))),
+    })
+}
+
+/// Returned by `poll_proceed`.
+pub(crate) struct RestoreOnPending(Cell<bool>);
+
+impl RestoreOnPending {
+    /// The operation didn't return `Pending`, so the unit stays spent.
+    pub(crate) fn made_progress(&self) {
+        self.0.set(false);
+    }
+}
+
+impl Drop for RestoreOnPending {
+    fn drop(&mut self) {
+        if self.0.get() {
+            CURRENT.with(|budget| budget.set(budget.get().map(|n| n + 1)));
+        }
+    }
+}
+
+/// Gives the other tasks on this thread a chance to run before the current
+/// one continues.
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+/// Returned by `yield_now`.
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Waker;
+
+    use super::*;
+
+    fn proceed() -> Poll<RestoreOnPending> {
+        poll_proceed(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn budget_runs_out() {
+        budget(|| {
+            for _ in 0..BUDGET {
+                let Poll::Ready(coop) = proceed() else {
+                    panic!("budget ran out early");
+                };
+                coop.made_progress();
+            }
+            assert!(proceed().is_pending());
+        });
+    }
+
+    #[test]
+    fn pending_operations_give_their_unit_back() {
+        budget(|| {
+            for _ in 0..2 * BUDGET as usize {
+                // Dropped without `made_progress`
+                assert!(proceed().is_ready());
+            }
+        });
+    }
+
+    #[test]
+    fn no_budget_outside_of_a_task() {
+        for _ in 0..2 * BUDGET as usize {
+            let Poll::Ready(coop) = proceed() else {
+                panic!("limited outside of a task");
+            };
+            coop.made_progress();
+        }
+    }
+}
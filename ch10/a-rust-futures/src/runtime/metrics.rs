@@ -0,0 +1,185 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+use super::context::Handle;
+
+/// Upper bounds of the buckets of the poll duration histogram. Polls slower
+/// than the last one go in an extra bucket.
+const POLL_BUCKETS: [Duration; 6] = [
+    Duration::from_micros(1),
+    Duration::from_micros(10),
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+];
+
+/// A snapshot of the metrics of the current runtime.
+///
+/// # Panics
+/// If called outside of a runtime context.
+pub fn metrics() -> RuntimeMetrics {
+    Handle::current().metrics()
+}
+
+/// What a runtime has been up to since it was built. Every counter only ever
+/// goes up, so take two snapshots and subtract to look at a time window.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RuntimeMetrics {
+    /// Calls to `mio::Poll::poll` by the reactor.
+    pub reactor_polls: u64,
+    /// IO events returned by those calls.
+    pub io_events: u64,
+    /// Wakers woken by IO events and expired timers.
+    pub wakeups: u64,
+    /// IO events that didn't wake anyone because nobody was waiting for
+    /// them. A lot of these means sources are ready before anyone asks.
+    pub events_without_waker: u64,
+    /// Times a spawned task was polled.
+    pub tasks_polled: u64,
+    /// Polls that returned `Pending`.
+    pub polls_pending: u64,
+    /// Polls that returned `Ready`, i.e. tasks that completed.
+    pub polls_ready: u64,
+    /// How long task polls took, as `(upper bound, count)` per bucket. The
+    /// last bucket has `Duration::MAX` as its bound.
+    pub poll_durations: Vec<(Duration, u64)>,
+    /// One entry per executor thread: the worker threads of a multi-thread
+    /// runtime, or the thread calling `block_on` of a current-thread runtime.
+    pub workers: Vec<WorkerMetrics>,
+}
+
+impl RuntimeMetrics {
+    pub fn events_per_poll(&self) -> f64 {
+        if self.reactor_polls == 0 {
+            return 0.0;
+        }
+        self.io_events as f64 / self.reactor_polls as f64
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WorkerMetrics {
+    /// Times the thread went to sleep because it had nothing to do.
+    pub parks: u64,
+    pub time_parked: Duration,
+}
+
+/// The counters behind `RuntimeMetrics`. Shared by the reactor and the
+/// executor of a runtime, and only ever updated with relaxed atomics.
+pub(crate) struct Metrics {
+    reactor_polls: AtomicU64,
+    io_events: AtomicU64,
+    wakeups: AtomicU64,
+    events_without_waker: AtomicU64,
+    tasks_polled: AtomicU64,
+    polls_pending: AtomicU64,
+    polls_ready: AtomicU64,
+    poll_durations: [AtomicU64; POLL_BUCKETS.len() + 1],
+    workers: Vec<WorkerCounters>,
+}
+
+#[derive(Default)]
+struct WorkerCounters {
+    parks: AtomicU64,
+    parked_nanos: AtomicU64,
+}
+
+impl Metrics {
+    pub(crate) fn new(workers: usize) -> Self {
+        Self {
+            reactor_polls: AtomicU64::default(),
+            io_events: AtomicU64::default(),
+            wakeups: AtomicU64::default(),
+            events_without_waker: AtomicU64::default(),
+            tasks_polled: AtomicU64::default(),
+            polls_pending: AtomicU64::default(),
+            polls_ready: AtomicU64::default(),
+            poll_durations: Default::default(),
+            workers: (0..workers).map(|_| WorkerCounters::default()).collect(),
+        }
+    }
+
+    pub(crate) fn reactor_polled(&self, events: usize) {
+        self.reactor_polls.fetch_add(1, Ordering::Relaxed);
+        self.io_events.fetch_add(events as u64, Ordering::Relaxed);
+    }
+
+    /// An IO event woke `woken` wakers.
+    pub(crate) fn event_delivered(&self, woken: usize) {
+        if woken == 0 {
+            self.events_without_waker.fetch_add(1, Ordering::Relaxed);
+        }
+        self.wakeups.fetch_add(woken as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn timers_fired(&self, n: usize) {
+        self.wakeups.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    /// Runs `poll`, which polls a task, and records how long it took and
+    /// what it returned.
+    pub(crate) fn time_poll<T>(&self, poll: impl FnOnce() -> Poll<T>) -> Poll<T> {
+        let start = Instant::now();
+        let res = poll();
+        let elapsed = start.elapsed();
+
+        self.tasks_polled.fetch_add(1, Ordering::Relaxed);
+        let outcome = match res {
+            Poll::Pending => &self.polls_pending,
+            Poll::Ready(_) => &self.polls_ready,
+        };
+        outcome.fetch_add(1, Ordering::Relaxed);
+        let bucket = POLL_BUCKETS
+            .iter()
+            .position(|bound| elapsed < *bound)
+            .unwrap_or(POLL_BUCKETS.len());
+        self.poll_durations[bucket].fetch_add(1, Ordering::Relaxed);
+        res
+    }
+
+    /// Runs `park`, which puts executor thread `worker` to sleep, and
+    /// records how long it slept.
+    pub(crate) fn time_park(&self, worker: usize, park: impl FnOnce()) {
+        let start = Instant::now();
+        park();
+        let elapsed = start.elapsed();
+
+        if let Some(counters) = self.workers.get(worker) {
+            counters.parks.fetch_add(1, Ordering::Relaxed);
+            counters
+                .parked_nanos
+                .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> RuntimeMetrics {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        RuntimeMetrics {
+            reactor_polls: load(&self.reactor_polls),
+            io_events: load(&self.io_events),
+            wakeups: load(&self.wakeups),
+            events_without_waker: load(&self.events_without_waker),
+            tasks_polled: load(&self.tasks_polled),
+            polls_pending: load(&self.polls_pending),
+            polls_ready: load(&self.polls_ready),
+            poll_durations: POLL_BUCKETS
+                .iter()
+                .chain([&Duration::MAX])
+                .zip(&self.poll_durations)
+                .map(|(bound, count)| (*bound, load(count)))
+                .collect(),
+            workers: self
+                .workers
+                .iter()
+                .map(|w| WorkerMetrics {
+                    parks: load(&w.parks),
+                    time_parked: Duration::from_nanos(load(&w.parked_nanos)),
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,571 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::Duration,
+};
+
+use tracing::Span;
+
+use super::{
+    builder::ThreadConfig,
+    context::Handle,
+    coop,
+    metrics::Metrics,
+    task::{self, JoinHandle, PanicHook},
+};
+
+type SendTask = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+// The states of a task, see `Task::state`. `IDLE` is waiting to be woken,
+// `SCHEDULED` is in one of the queues, `RUNNING` is being polled, and
+// `NOTIFIED` was woken while being polled, so the worker polling it puts it
+// back in a queue when it's done.
+const IDLE: u8 = 0;
+const SCHEDULED: u8 = 1;
+const RUNNING: u8 = 2;
+const NOTIFIED: u8 = 3;
+const COMPLETE: u8 = 4;
+
+thread_local! {
+    // Set on the worker threads so we know which local queue to use. The
+    // thread blocked in `Runtime::block_on` sets it too, without a queue.
+    static WORKER: RefCell<Option<(Arc<Shared>, Option<usize>)>> = const { RefCell::new(None) };
+}
+
+struct Task {
+    id: usize,
+    // Only ever locked by the worker that's polling the task, or on shutdown
+    future: Mutex<Option<SendTask>>,
+    // One of `IDLE`, `SCHEDULED`... A task is never queued twice, and a task
+    // that's woken while it's polled isn't queued until the poll is done,
+    // so no other worker ever waits for it
+    state: AtomicU8,
+    shared: Arc<Shared>,
+    // Entered while the task is polled
+    span: Span,
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            let next = match state {
+                IDLE => SCHEDULED,
+                RUNNING => NOTIFIED,
+                // Still write when nothing changes, so whatever we did before
+                // waking is visible to the next poll
+                _ => state,
+            };
+            match self
+                .state
+                .compare_exchange_weak(state, next, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => break,
+                Err(actual) => state = actual,
+            }
+        }
+
+        match state {
+            IDLE => {
+                tracing::trace!(task.id = self.id, "woken");
+                self.shared.schedule(self.clone());
+            }
+            RUNNING => tracing::trace!(task.id = self.id, "woken while running"),
+            _ => (),
+        }
+    }
+}
+
+struct Shared {
+    // Tasks scheduled from outside the runtime end up here
+    injector: Mutex<VecDeque<Arc<Task>>>,
+    // One queue per worker. The owner pops from the front, thieves take
+    // from the back.
+    locals: Vec<Mutex<VecDeque<Arc<Task>>>>,
+    // Every task that hasn't completed yet, so we can drop them on shutdown
+    tasks: Mutex<HashMap<usize, Arc<Task>>>,
+    // Notified when `tasks` becomes empty
+    all_done: Condvar,
+    next_id: AtomicUsize,
+    // Number of workers that are (about to be) waiting on `condvar`
+    sleepers: AtomicUsize,
+    sleep_lock: Mutex<()>,
+    condvar: Condvar,
+    shutdown: AtomicBool,
+    panic_hook: Option<PanicHook>,
+    metrics: Arc<Metrics>,
+}
+
+impl Shared {
+    fn schedule(self: &Arc<Self>, task: Arc<Task>) {
+        let local = WORKER.with(|w| match &*w.borrow() {
+            Some((shared, index)) if Arc::ptr_eq(shared, self) => *index,
+            _ => None,
+        });
+
+        let queue = match local {
+            Some(index) => &self.locals[index],
+            None => &self.injector,
+        };
+        let mut queue = queue.lock().unwrap();
+        // `stop` sets the flag before it empties the queues, so with the queue
+        // locked we either see it or our task is emptied out with the rest
+        if self.shutdown.load(Ordering::Acquire) {
+            drop(queue);
+            self.release(&task);
+            return;
+        }
+        queue.push_back(task);
+        drop(queue);
+
+        // The queue lock above is taken before we read `sleepers`, and a
+        // worker increments `sleepers` before it checks the queues, so
+        // either it sees our task or we see that it's going to sleep.
+        if self.sleepers.load(Ordering::SeqCst) > 0 {
+            let _guard = self.sleep_lock.lock().unwrap();
+            self.condvar.notify_one();
+        }
+    }
+
+    /// Drops a task that was woken or spawned after shutdown. Nothing would
+    /// ever take it out of a queue again, and a queued task keeps `Shared`
+    /// alive through its own reference to it.
+    fn release(&self, task: &Task) {
+        self.tasks.lock().unwrap().remove(&task.id);
+        // If it's being polled right now, `run_task` drops it once it returns
+        let future = task.future.try_lock().ok().and_then(|mut f| f.take());
+        drop(future);
+    }
+
+    fn has_work(&self) -> bool {
+        !self.injector.lock().unwrap().is_empty()
+            || self.locals.iter().any(|q| !q.lock().unwrap().is_empty())
+    }
+
+    /// Local queue first, then the injector, then try to steal from the
+    /// other workers.
+    fn next_task(&self, index: usize) -> Option<Arc<Task>> {
+        if let Some(task) = self.locals[index].lock().unwrap().pop_front() {
+            return Some(task);
+        }
+
+        if let Some(task) = self.injector.lock().unwrap().pop_front() {
+            return Some(task);
+        }
+
+        self.steal(index)
+    }
+
+    /// Takes half of the tasks from the back of the first worker that has
+    /// any, keeps one to run now and puts the rest in our own queue.
+    fn steal(&self, index: usize) -> Option<Arc<Task>> {
+        let n = self.locals.len();
+        for victim in (1..n).map(|i| (index + i) % n) {
+            let mut stolen = {
+                let mut victim = self.locals[victim].lock().unwrap();
+                let len = victim.len();
+                if len == 0 {
+                    continue;
+                }
+                victim.split_off(len - len.div_ceil(2))
+            };
+
+            let task = stolen.pop_front();
+            self.locals[index].lock().unwrap().extend(stolen);
+            return task;
+        }
+
+        None
+    }
+
+    fn run_task(&self, task: Arc<Task>) {
+        // Nobody else changes the state of a queued task, and a wake during
+        // `poll` makes it `NOTIFIED`
+        task.state.swap(RUNNING, Ordering::AcqRel);
+        let waker = Waker::from(task.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut slot = task.future.lock().unwrap();
+        let Some(mut future) = slot.take() else {
+            // Completed, or dropped on shutdown
+            return;
+        };
+
+        let res = {
+            let _span = task.span.enter();
+            coop::budget(|| self.metrics.time_poll(|| future.as_mut().poll(&mut cx)))
+        };
+        match res {
+            // The runtime was shut down from inside the task, nobody will
+            // poll it again
+            Poll::Pending if self.shutdown.load(Ordering::Acquire) => drop(future),
+            Poll::Pending => {
+                *slot = Some(future);
+                drop(slot);
+                let idle =
+                    task.state
+                        .compare_exchange(RUNNING, IDLE, Ordering::AcqRel, Ordering::Acquire);
+                if idle.is_err() {
+                    // Woken while we polled it
+                    task.state.swap(SCHEDULED, Ordering::AcqRel);
+                    task.shared.schedule(task.clone());
+                }
+            }
+            Poll::Ready(()) => {
+                task.state.store(COMPLETE, Ordering::Release);
+                tracing::trace!(parent: &task.span, "completed");
+                let mut tasks = self.tasks.lock().unwrap();
+                tasks.remove(&task.id);
+                if tasks.is_empty() {
+                    self.all_done.notify_all();
+                }
+            }
+        }
+    }
+
+    fn park(&self, index: usize) {
+        let guard = self.sleep_lock.lock().unwrap();
+        self.sleepers.fetch_add(1, Ordering::SeqCst);
+        if !self.has_work() && !self.shutdown.load(Ordering::SeqCst) {
+            tracing::debug!("no tasks to run or steal, sleeping until notified");
+            self.metrics.time_park(index, || {
+                drop(self.condvar.wait(guard).unwrap());
+            });
+        }
+        self.sleepers.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn worker(shared: Arc<Shared>, index: usize, runtime: Option<Handle>) {
+    let _worker = WorkerGuard::new(shared.clone(), Some(index));
+    let _guard = runtime.as_ref().map(|h| h.enter());
+
+    while !shared.shutdown.load(Ordering::Acquire) {
+        match shared.next_task(index) {
+            Some(task) => shared.run_task(task),
+            None => shared.park(index),
+        }
+    }
+}
+
+/// Sets `WORKER` until dropped, then puts back whatever was there before.
+pub(crate) struct WorkerGuard {
+    prev: Option<(Arc<Shared>, Option<usize>)>,
+}
+
+impl WorkerGuard {
+    fn new(shared: Arc<Shared>, index: Option<usize>) -> Self {
+        let prev = WORKER.with(|w| w.borrow_mut().replace((shared, index)));
+        Self { prev }
+    }
+}
+
+impl Drop for WorkerGuard {
+    fn drop(&mut self) {
+        WORKER.with(|w| *w.borrow_mut() = self.prev.take());
+    }
+}
+
+/// An executor that runs `Send` tasks on a pool of worker threads.
+///
+/// Each worker has its own queue that it takes tasks from first. Tasks
+/// spawned or woken from outside the runtime go in a global "injector"
+/// queue, and a worker with nothing to do steals half of the queue of
+/// another worker before going to sleep.
+pub struct MultiThreadExecutor {
+    handle: MultiThreadHandle,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl MultiThreadExecutor {
+    /// Starts `worker_threads` worker threads named `exec-0`, `exec-1`...
+    ///
+    /// If this is called from within a runtime context, the workers use the
+    /// reactor of that runtime.
+    pub fn new(worker_threads: usize) -> Self {
+        Self::with_config(
+            worker_threads,
+            &ThreadConfig::default(),
+            None,
+            Handle::try_current(),
+            Arc::new(Metrics::new(worker_threads)),
+        )
+    }
+
+    pub(crate) fn with_config(
+        worker_threads: usize,
+        threads: &ThreadConfig,
+        panic_hook: Option<PanicHook>,
+        runtime: Option<Handle>,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        assert!(worker_threads > 0, "need at least one worker thread");
+
+        let shared = Arc::new(Shared {
+            injector: Mutex::default(),
+            locals: (0..worker_threads).map(|_| Mutex::default()).collect(),
+            tasks: Mutex::default(),
+            all_done: Condvar::new(),
+            next_id: AtomicUsize::new(0),
+            sleepers: AtomicUsize::new(0),
+            sleep_lock: Mutex::new(()),
+            condvar: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+            panic_hook,
+            metrics,
+        });
+
+        let workers = (0..worker_threads)
+            .map(|i| {
+                let shared = shared.clone();
+                let runtime = runtime.clone();
+                let name = threads.name_or(&format!("exec-{i}"), &format!("-{i}"));
+                threads.spawn(name, move || worker(shared, i, runtime))
+            })
+            .collect();
+
+        Self {
+            handle: MultiThreadHandle { shared },
+            workers,
+        }
+    }
+
+    /// A handle that can be cloned and moved to other threads to spawn tasks
+    /// on this executor.
+    pub fn handle(&self) -> MultiThreadHandle {
+        self.handle.clone()
+    }
+
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.handle.spawn(future)
+    }
+
+    /// Spawns `future` on the worker threads and blocks the current thread
+    /// until it has completed.
+    pub fn block_on<F>(&self, future: F) -> F::Output
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match park_on(self.spawn(future)) {
+            Ok(output) => output,
+            Err(e) => panic!("block_on: {e}"),
+        }
+    }
+
+    /// Makes `MultiThreadHandle::current` work on this thread, which isn't
+    /// a worker, until the guard is dropped.
+    pub(crate) fn enter(&self) -> WorkerGuard {
+        WorkerGuard::new(self.handle.shared.clone(), None)
+    }
+
+    /// Waits up to `timeout` for the remaining tasks to complete and then
+    /// stops the workers and drops whatever is left.
+    pub(crate) fn shutdown(&mut self, timeout: Duration) {
+        let shared = &self.handle.shared;
+        let tasks = shared.tasks.lock().unwrap();
+        let _ = shared
+            .all_done
+            .wait_timeout_while(tasks, timeout, |tasks| !tasks.is_empty())
+            .unwrap();
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        let shared = &self.handle.shared;
+        shared.shutdown.store(true, Ordering::Release);
+        {
+            let _guard = shared.sleep_lock.lock().unwrap();
+            shared.condvar.notify_all();
+        }
+
+        // If one of our own tasks is shutting us down, its worker stops once
+        // the task returns, and joining it here would wait forever
+        let current = WORKER.with(|w| match &*w.borrow() {
+            Some((s, index)) if Arc::ptr_eq(s, shared) => *index,
+            _ => None,
+        });
+        for (index, worker) in self.workers.drain(..).enumerate() {
+            if Some(index) != current {
+                let _ = worker.join();
+            }
+        }
+
+        // Drop every future that didn't complete. Tasks hold a reference to
+        // `Shared`, so we also need to empty the queues to break the cycle.
+        let tasks: Vec<_> = shared
+            .tasks
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, t)| t)
+            .collect();
+        for task in tasks {
+            // The task that's shutting us down is still being polled, and
+            // `run_task` drops it once it returns
+            if let Ok(mut future) = task.future.try_lock() {
+                future.take();
+            }
+        }
+        shared.injector.lock().unwrap().clear();
+        for local in &shared.locals {
+            local.lock().unwrap().clear();
+        }
+    }
+}
+
+impl Drop for MultiThreadExecutor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Spawns tasks on a `MultiThreadExecutor` from any thread.
+#[derive(Clone)]
+pub struct MultiThreadHandle {
+    shared: Arc<Shared>,
+}
+
+impl MultiThreadHandle {
+    /// The handle of the executor the current worker thread belongs to, or
+    /// of the multi-thread `Runtime` we're in `block_on` of.
+    ///
+    /// # Panics
+    /// If called from anywhere else.
+    pub fn current() -> Self {
+        WORKER.with(|w| match &*w.borrow() {
+            Some((shared, _)) => Self {
+                shared: shared.clone(),
+            },
+            None => panic!("MultiThreadHandle::current called outside a worker thread"),
+        })
+    }
+
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (harness, handle) = task::new_task(future, self.shared.panic_hook.clone());
+        let id = self.shared.next_id.fetch_add(1, Ordering::Relaxed);
+        let span = tracing::trace_span!("task", id);
+        tracing::trace!(parent: &span, "spawned");
+        let task = Arc::new(Task {
+            id,
+            future: Mutex::new(Some(Box::pin(harness))),
+            state: AtomicU8::new(SCHEDULED),
+            shared: self.shared.clone(),
+            span,
+        });
+
+        self.shared
+            .tasks
+            .lock()
+            .unwrap()
+            .insert(task.id, task.clone());
+        self.shared.schedule(task);
+        handle
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls `future` on the current thread, parking it in between polls.
+pub(crate) fn park_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => break output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future;
+
+    use super::*;
+
+    /// Sets its flag when dropped.
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Release);
+        }
+    }
+
+    /// Stays pending forever, and hands its waker to `waker`.
+    fn pending(
+        dropped: &Arc<AtomicBool>,
+        waker: &Arc<Mutex<Option<Waker>>>,
+    ) -> impl Future<Output = ()> + Send {
+        let flag = DropFlag(dropped.clone());
+        let waker = waker.clone();
+        future::poll_fn(move |cx| {
+            let _ = &flag;
+            *waker.lock().unwrap() = Some(cx.waker().clone());
+            Poll::Pending
+        })
+    }
+
+    #[test]
+    fn tasks_woken_after_shutdown_are_dropped() {
+        let executor = MultiThreadExecutor::new(1);
+        let handle = executor.handle();
+        let dropped = Arc::new(AtomicBool::new(false));
+        let waker = Arc::new(Mutex::new(None));
+        executor.spawn(pending(&dropped, &waker));
+        while waker.lock().unwrap().is_none() {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        drop(executor);
+        assert!(dropped.load(Ordering::Acquire));
+        waker.lock().unwrap().take().unwrap().wake();
+
+        // Nothing but `handle` is left holding on to `Shared`
+        assert!(handle.shared.injector.lock().unwrap().is_empty());
+        assert_eq!(Arc::strong_count(&handle.shared), 1);
+    }
+
+    #[test]
+    fn tasks_spawned_after_shutdown_are_dropped() {
+        let executor = MultiThreadExecutor::new(1);
+        let handle = executor.handle();
+        drop(executor);
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let join = handle.spawn(pending(&dropped, &Arc::default()));
+        assert!(dropped.load(Ordering::Acquire));
+        assert!(park_on(join).unwrap_err().is_cancelled());
+        assert!(handle.shared.tasks.lock().unwrap().is_empty());
+        assert_eq!(Arc::strong_count(&handle.shared), 1);
+    }
+}
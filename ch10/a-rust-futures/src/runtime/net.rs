@@ -0,0 +1,13 @@
+//! TCP and UDP sockets that suspend the task instead of blocking the thread
+//! when they're not ready.
+//!
+//! They're registered with the reactor of the current runtime when created
+//! and deregistered when dropped, so they have to be created inside a
+//! runtime context with IO enabled.
+
+pub use tcp::{TcpListener, TcpStream};
+pub use udp::UdpSocket;
+
+mod io_source;
+mod tcp;
+mod udp;
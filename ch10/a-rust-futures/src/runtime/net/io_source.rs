@@ -0,0 +1,60 @@
+use std::{
+    io::{self, ErrorKind},
+    os::fd::AsRawFd,
+    task::{ready, Context, Poll},
+};
+
+use mio::{event::Source, Interest};
+
+pub(crate) use crate::runtime::scheduled_io::Direction;
+use crate::runtime::{context::reactor, coop, reactor::Registration};
+
+/// A mio source registered with the reactor of the current runtime. Does
+/// the "try it, and if it would block, wait for the reactor" dance for the
+/// socket types, and deregisters the source when dropped.
+pub(crate) struct IoSource<S: Source + AsRawFd> {
+    // Declared first so it's dropped, and deregisters `io`, before `io` is
+    // closed
+    registration: Registration,
+    io: S,
+}
+
+impl<S: Source + AsRawFd> IoSource<S> {
+    /// # Panics
+    /// If called outside of a runtime context with IO enabled.
+    pub(crate) fn new(mut io: S, interest: Interest) -> io::Result<Self> {
+        let registration = reactor().register(&mut io, interest)?;
+        Ok(Self { registration, io })
+    }
+
+    pub(crate) fn get_ref(&self) -> &S {
+        &self.io
+    }
+
+    /// Waits until the reactor says the source is ready in `direction`, then
+    /// runs `f`. If `f` returns `WouldBlock` the readiness is cleared and we
+    /// wait for the next event. Every call that doesn't return `Pending`
+    /// spends some of the task's budget, see `coop`.
+    pub(crate) fn poll_io<R>(
+        &self,
+        direction: Direction,
+        cx: &mut Context,
+        mut f: impl FnMut(&S) -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        let coop = ready!(coop::poll_proceed(cx));
+        loop {
+            let event = ready!(self.registration.scheduled_io().poll_ready(direction, cx));
+
+            match f(&self.io) {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    self.registration.scheduled_io().clear_readiness(event)
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                res => {
+                    coop.made_progress();
+                    return Poll::Ready(res);
+                }
+            }
+        }
+    }
+}
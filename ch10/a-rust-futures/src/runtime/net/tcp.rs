@@ -0,0 +1,186 @@
+use std::{
+    future,
+    io::{self, ErrorKind, Read, Write},
+    net::{Shutdown, SocketAddr, ToSocketAddrs},
+    task::{Context, Poll},
+};
+
+use mio::Interest;
+
+use super::io_source::{Direction, IoSource};
+
+/// Resolves `addr` and calls `f` with each address until one works.
+pub(super) fn each_addr<T>(
+    addr: impl ToSocketAddrs,
+    mut f: impl FnMut(SocketAddr) -> io::Result<T>,
+) -> io::Result<T> {
+    let mut last_err = None;
+    for addr in addr.to_socket_addrs()? {
+        match f(addr) {
+            Ok(t) => return Ok(t),
+            Err(e) => last_err = Some(e),
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(ErrorKind::InvalidInput, "could not resolve to any address")
+    }))
+}
+
+/// A TCP connection. All methods take `&self`, so one task can read while
+/// another one writes, for example by putting the stream in an `Arc`.
+pub struct TcpStream {
+    source: IoSource<mio::net::TcpStream>,
+}
+
+impl TcpStream {
+    /// Opens a connection to `addr`.
+    ///
+    /// Resolving `addr` is done on the current thread and might block.
+    pub async fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let addrs: Vec<_> = addr.to_socket_addrs()?.collect();
+        let mut last_err = None;
+        for addr in addrs {
+            match Self::connect_addr(addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(ErrorKind::InvalidInput, "could not resolve to any address")
+        }))
+    }
+
+    async fn connect_addr(addr: SocketAddr) -> io::Result<Self> {
+        let stream = Self::new(mio::net::TcpStream::connect(addr)?)?;
+
+        // The connection is established (or has failed) once the socket
+        // becomes writable
+        future::poll_fn(|cx| {
+            stream.source.poll_io(Direction::Write, cx, |s| {
+                if let Some(e) = s.take_error()? {
+                    return Err(e);
+                }
+                match s.peer_addr() {
+                    Ok(_) => Ok(()),
+                    Err(e) if e.kind() == ErrorKind::NotConnected => {
+                        Err(ErrorKind::WouldBlock.into())
+                    }
+                    Err(e) => Err(e),
+                }
+            })
+        })
+        .await?;
+
+        Ok(stream)
+    }
+
+    fn new(stream: mio::net::TcpStream) -> io::Result<Self> {
+        Ok(Self {
+            source: IoSource::new(stream, Interest::READABLE | Interest::WRITABLE)?,
+        })
+    }
+
+    /// Reads into `buf` and returns how many bytes were read. `0` means the
+    /// other side closed the connection.
+    pub fn poll_read(&self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.source
+            .poll_io(Direction::Read, cx, |mut s| s.read(buf))
+    }
+
+    /// Writes some of `buf` and returns how many bytes were written.
+    pub fn poll_write(&self, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.source
+            .poll_io(Direction::Write, cx, |mut s| s.write(buf))
+    }
+
+    /// Nothing is buffered, so this is always ready.
+    pub fn poll_flush(&self, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    /// Shuts down the writing side, so the other side reads EOF.
+    pub fn poll_shutdown(&self, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(self.source.get_ref().shutdown(Shutdown::Write))
+    }
+
+    pub async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        future::poll_fn(|cx| self.poll_read(cx, buf)).await
+    }
+
+    /// Reads until the other side closes the connection.
+    pub async fn read_to_end(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let mut chunk = [0u8; 4096];
+        let mut total = 0;
+        loop {
+            match self.read(&mut chunk).await? {
+                0 => return Ok(total),
+                n => {
+                    buf.extend_from_slice(&chunk[..n]);
+                    total += n;
+                }
+            }
+        }
+    }
+
+    pub async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        future::poll_fn(|cx| self.poll_write(cx, buf)).await
+    }
+
+    pub async fn write_all(&self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf).await? {
+                0 => return Err(ErrorKind::WriteZero.into()),
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn shutdown(&self) -> io::Result<()> {
+        future::poll_fn(|cx| self.poll_shutdown(cx)).await
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.source.get_ref().peer_addr()
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.source.get_ref().local_addr()
+    }
+}
+
+/// Listens for incoming TCP connections.
+pub struct TcpListener {
+    source: IoSource<mio::net::TcpListener>,
+}
+
+impl TcpListener {
+    /// Binds to `addr`. Use port `0` to let the OS pick one, and
+    /// `local_addr` to find out which.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = each_addr(addr, mio::net::TcpListener::bind)?;
+        Ok(Self {
+            source: IoSource::new(listener, Interest::READABLE)?,
+        })
+    }
+
+    pub fn poll_accept(&self, cx: &mut Context) -> Poll<io::Result<(TcpStream, SocketAddr)>> {
+        self.source
+            .poll_io(Direction::Read, cx, |l| l.accept())
+            .map(|res| {
+                let (stream, addr) = res?;
+                Ok((TcpStream::new(stream)?, addr))
+            })
+    }
+
+    /// Waits for the next connection.
+    pub async fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        future::poll_fn(|cx| self.poll_accept(cx)).await
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.source.get_ref().local_addr()
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    future, io,
+    net::{SocketAddr, ToSocketAddrs},
+    task::{Context, Poll},
+};
+
+use mio::Interest;
+
+use super::{
+    io_source::{Direction, IoSource},
+    tcp::each_addr,
+};
+
+/// A UDP socket. Like `TcpStream`, all methods take `&self`, so sending and
+/// receiving can happen in different tasks.
+pub struct UdpSocket {
+    source: IoSource<mio::net::UdpSocket>,
+}
+
+impl UdpSocket {
+    /// Binds to `addr`. Use port `0` to let the OS pick one.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let socket = each_addr(addr, mio::net::UdpSocket::bind)?;
+        Ok(Self {
+            source: IoSource::new(socket, Interest::READABLE | Interest::WRITABLE)?,
+        })
+    }
+
+    /// Sets the default address for `send` and the only address `recv`
+    /// receives from.
+    pub fn connect(&self, addr: impl ToSocketAddrs) -> io::Result<()> {
+        each_addr(addr, |addr| self.source.get_ref().connect(addr))
+    }
+
+    pub fn poll_send_to(
+        &self,
+        cx: &mut Context,
+        buf: &[u8],
+        target: SocketAddr,
+    ) -> Poll<io::Result<usize>> {
+        self.source
+            .poll_io(Direction::Write, cx, |s| s.send_to(buf, target))
+    }
+
+    pub fn poll_recv_from(
+        &self,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<(usize, SocketAddr)>> {
+        self.source
+            .poll_io(Direction::Read, cx, |s| s.recv_from(buf))
+    }
+
+    /// Sends to the address we're connected to.
+    pub fn poll_send(&self, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.source.poll_io(Direction::Write, cx, |s| s.send(buf))
+    }
+
+    /// Receives from the address we're connected to.
+    pub fn poll_recv(&self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.source.poll_io(Direction::Read, cx, |s| s.recv(buf))
+    }
+
+    pub async fn send_to(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        future::poll_fn(|cx| self.poll_send_to(cx, buf, target)).await
+    }
+
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        future::poll_fn(|cx| self.poll_recv_from(cx, buf)).await
+    }
+
+    pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        future::poll_fn(|cx| self.poll_send(cx, buf)).await
+    }
+
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        future::poll_fn(|cx| self.poll_recv(cx, buf)).await
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.source.get_ref().local_addr()
+    }
+}
@@ -1,82 +1,347 @@
-use mio::{net::TcpStream, Events, Interest, Poll, Registry, Token};
+use mio::{event::Source, unix::SourceFd, Events, Interest, Poll, Registry, Token};
 use std::{
-    collections::HashMap,
+    io::{self, ErrorKind},
+    os::fd::{AsRawFd, RawFd},
     sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc, Mutex, OnceLock,
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
     },
-    thread, task::{Context, Waker},
+    task::{Context, Waker},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
+use self::slab::Slab;
+use super::{
+    metrics::Metrics,
+    scheduled_io::ScheduledIo,
+    time::{TimerKey, Timers},
+};
 
-type Wakers = Arc<Mutex<HashMap<usize, Waker>>>;
-
-static REACTOR: OnceLock<Reactor> = OnceLock::new();
+mod slab;
 
-pub fn reactor() -> &'static Reactor {
-    REACTOR.get().expect("Called outside an runtime context")
-}
+/// Reserved for the `mio::Waker` we use to interrupt `event_loop`. The slab
+/// never hands out 0 as a key.
+const WAKE_TOKEN: Token = Token(0);
 
 pub struct Reactor {
-    wakers: Wakers,
+    // Keyed by the token the source is registered with
+    sources: Mutex<Slab<Arc<ScheduledIo>>>,
     registry: Registry,
-    next_id: AtomicUsize,
+    waker: mio::Waker,
+    shutdown: AtomicBool,
+    timers: Mutex<Timers>,
+    io_enabled: bool,
+    time_enabled: bool,
+    metrics: Arc<Metrics>,
 }
 
 impl Reactor {
-    pub fn register(&self, stream: &mut TcpStream, interest: Interest, id: usize) {
-        self.registry.register(stream, Token(id), interest).unwrap();
+    /// Registers `source` with the OS poller. Events for it are delivered to
+    /// the returned `Registration`, which deregisters the source when it's
+    /// dropped.
+    pub fn register(
+        self: &Arc<Self>,
+        source: &mut (impl Source + AsRawFd),
+        interest: Interest,
+    ) -> io::Result<Registration> {
+        assert!(
+            self.io_enabled,
+            "IO is disabled on this runtime, call `Builder::enable_io`"
+        );
+        let scheduled_io = Arc::new(ScheduledIo::default());
+        let id = self.sources.lock().unwrap().insert(scheduled_io.clone());
+        if let Err(e) = self.registry.register(source, Token(id), interest) {
+            self.sources.lock().unwrap().remove(id);
+            return Err(e);
+        }
+        tracing::trace!(token = id, ?interest, "registered");
+
+        Ok(Registration {
+            reactor: self.clone(),
+            id,
+            scheduled_io,
+            fd: source.as_raw_fd(),
+            deregistered: AtomicBool::new(false),
+        })
     }
 
-    pub fn set_waker(&self, cx: &Context, id: usize) {
-        let _ = self
-            .wakers
-            .lock()
-            .map(|mut w| w.insert(id, cx.waker().clone()).is_none())
-            .unwrap();
+    /// Counts what's currently registered. Meant for tests and debugging,
+    /// e.g. to check that dropped futures don't leave anything behind.
+    pub fn stats(&self) -> ReactorStats {
+        ReactorStats {
+            registrations: self.sources.lock().unwrap().len(),
+            timers: self.timers.lock().unwrap().len(),
+        }
     }
 
-    pub fn deregister(&self, stream: &mut TcpStream, id: usize) {
-        self.wakers.lock().map(|mut w| w.remove(&id)).unwrap();
-        self.registry.deregister(stream).unwrap();
+    pub(crate) fn add_timer(&self, deadline: Instant, waker: Waker) -> TimerKey {
+        assert!(
+            self.time_enabled,
+            "time is disabled on this runtime, call `Builder::enable_time`"
+        );
+        let (key, is_first) = self.timers.lock().unwrap().insert(deadline, waker);
+        // `Driver::turn` might be blocked with a timeout computed from a
+        // later deadline, so we have to interrupt it
+        if is_first {
+            self.unpark();
+        }
+        key
     }
 
-    pub fn next_id(&self) -> usize {
-        self.next_id.fetch_add(1, Ordering::Relaxed)
+    pub(crate) fn set_timer_waker(&self, key: TimerKey, waker: &Waker) {
+        self.timers.lock().unwrap().set_waker(key, waker);
     }
+
+    pub(crate) fn remove_timer(&self, key: TimerKey) {
+        self.timers.lock().unwrap().remove(key);
+    }
+
+    /// Makes a `Driver::turn` that's blocked, or the next one, return right
+    /// away.
+    pub(crate) fn unpark(&self) {
+        self.waker.wake().unwrap();
+    }
+
+    /// Makes `event_loop` return. The thread can then be joined using the
+    /// handle returned from `start`.
+    pub(crate) fn stop(&self) {
+        self.shutdown.store(true, Ordering::Release);
+        self.unpark();
+    }
+}
+
+/// What's registered with a reactor right now, see `Reactor::stats`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReactorStats {
+    pub registrations: usize,
+    pub timers: usize,
 }
 
-fn event_loop(mut poll: Poll, wakers: Wakers) {
-    let mut events = Events::with_capacity(100);
-    loop {
-        poll.poll(&mut events, None).unwrap();
-        for e in events.iter() {
+/// A source registered with a reactor, returned by `Reactor::register`.
+///
+/// Dropping it removes the source from the OS poller, unless `deregister`
+/// already did, and the wakers stored for it from the reactor, so a future
+/// that's dropped halfway through doesn't leave anything behind. It has to be
+/// dropped before the source is closed, since by then the file descriptor
+/// might belong to another source.
+pub struct Registration {
+    reactor: Arc<Reactor>,
+    id: usize,
+    scheduled_io: Arc<ScheduledIo>,
+    fd: RawFd,
+    deregistered: AtomicBool,
+}
+
+impl Registration {
+    /// Wakes the task on the next event for the source, whatever the
+    /// direction. Only on the next one, so store the waker again before
+    /// every wait, and retry the operation once it's stored in case the
+    /// event came in before.
+    pub fn set_waker(&self, cx: &Context) {
+        self.scheduled_io.set_waker(cx.waker());
+    }
+
+    /// Removes `source` from the OS poller. Must be the source this was
+    /// returned for.
+    pub fn deregister(&self, source: &mut impl Source) -> io::Result<()> {
+        self.deregistered.store(true, Ordering::Relaxed);
+        self.reactor.registry.deregister(source)?;
+        tracing::trace!(token = self.id, "deregistered");
+        Ok(())
+    }
+
+    pub(crate) fn scheduled_io(&self) -> &ScheduledIo {
+        &self.scheduled_io
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if !*self.deregistered.get_mut() {
+            // Nothing we can do about it here, and the source is going away
+            // anyway
+            if let Err(e) = self.reactor.registry.deregister(&mut SourceFd(&self.fd)) {
+                tracing::debug!(token = self.id, error = %e, "deregistering failed");
+            }
+        }
+        self.reactor.sources.lock().unwrap().remove(self.id);
+        tracing::trace!(token = self.id, "registration dropped");
+    }
+}
+
+/// Owns the `mio::Poll` of a reactor and turns what it reports into
+/// wakeups. It's driven either by the reactor thread, see `start`, or by the
+/// executor itself when there's no reactor thread.
+pub(crate) struct Driver {
+    reactor: Arc<Reactor>,
+    poll: Poll,
+    events: Events,
+}
+
+impl Driver {
+    pub(crate) fn reactor(&self) -> &Arc<Reactor> {
+        &self.reactor
+    }
+
+    /// Waits for IO events for at most `timeout` (forever if `None`), or
+    /// until the next timer expires, and wakes the tasks waiting for them.
+    /// Also returns when `Reactor::unpark` is called.
+    pub(crate) fn turn(&mut self, timeout: Option<Duration>) {
+        let next_timer = self
+            .reactor
+            .timers
+            .lock()
+            .unwrap()
+            .next_timeout(Instant::now());
+        let timeout = match (timeout, next_timer) {
+            (Some(timeout), Some(next_timer)) => Some(timeout.min(next_timer)),
+            (timeout, next_timer) => timeout.or(next_timer),
+        };
+
+        match self.poll.poll(&mut self.events, timeout) {
+            Ok(()) => (),
+            // A signal, we'll be called again soon enough
+            Err(e) if e.kind() == ErrorKind::Interrupted => (),
+            Err(e) => panic!("polling for events failed: {e}"),
+        }
+
+        let expired = self.reactor.timers.lock().unwrap().expired(Instant::now());
+        self.reactor.metrics.timers_fired(expired.len());
+        for waker in expired {
+            waker.wake();
+        }
+
+        self.dispatch_events();
+    }
+
+    /// Wakes the tasks waiting for the IO events in `events`.
+    fn dispatch_events(&self) {
+        let metrics = &self.reactor.metrics;
+        let mut io_events = 0;
+        for e in self.events.iter() {
+            if e.token() == WAKE_TOKEN {
+                continue;
+            }
+            io_events += 1;
+
             // Optimization for Windows since we get unneeded wakeups
             // if !e.is_readable() && e.is_read_closed() {
             //     continue;
             // }
+            // `None` if the source was dropped after the event was queued
             let Token(id) = e.token();
-            let wakers = wakers.lock().unwrap();
+            let io = self.reactor.sources.lock().unwrap().get(id).cloned();
 
-            if let Some(waker) = wakers.get(&id) {
-                waker.wake_by_ref();
-            }
+            // Wakes the reader and/or the writer, depending on the event
+            let woken = io.map_or(0, |io| io.set_readiness(e));
+            metrics.event_delivered(woken);
+            tracing::trace!(
+                token = id,
+                readable = e.is_readable(),
+                writable = e.is_writable(),
+                woken,
+                "event"
+            );
         }
+        metrics.reactor_polled(io_events);
     }
 }
 
-pub fn start() {
-    use thread::spawn;
-    let wakers = Arc::new(Mutex::new(HashMap::new()));
+fn event_loop(mut driver: Driver) {
+    while !driver.reactor.shutdown.load(Ordering::Acquire) {
+        driver.turn(None);
+    }
+}
+
+/// Creates a new reactor without starting a thread for it. Whoever owns the
+/// returned `Driver` has to call `turn` for anything to happen.
+/// `event_capacity` is the most events handled per call to `Poll::poll`.
+pub(crate) fn new(
+    event_capacity: usize,
+    enable_io: bool,
+    enable_time: bool,
+    metrics: Arc<Metrics>,
+) -> Driver {
     let poll = Poll::new().unwrap();
     let registry = poll.registry().try_clone().unwrap();
-    let next_id = AtomicUsize::new(1);
-    let reactor = Reactor {
-        wakers: wakers.clone(),
+    let waker = mio::Waker::new(&registry, WAKE_TOKEN).unwrap();
+    let reactor = Arc::new(Reactor {
+        sources: Mutex::new(Slab::new()),
         registry,
-        next_id,
-    };
+        waker,
+        shutdown: AtomicBool::new(false),
+        timers: Mutex::default(),
+        io_enabled: enable_io,
+        time_enabled: enable_time,
+        metrics,
+    });
+
+    Driver {
+        reactor,
+        poll,
+        events: Events::with_capacity(event_capacity),
+    }
+}
+
+/// Creates a new reactor and starts its event loop on a new thread called
+/// `thread_name`. `event_capacity` is the most events handled per call to
+/// `Poll::poll`.
+pub fn start(
+    event_capacity: usize,
+    enable_io: bool,
+    enable_time: bool,
+    thread_name: String,
+    metrics: Arc<Metrics>,
+) -> (Arc<Reactor>, JoinHandle<()>) {
+    let driver = new(event_capacity, enable_io, enable_time, metrics);
+    let reactor = driver.reactor().clone();
+    let handle = thread::Builder::new()
+        .name(thread_name)
+        .spawn(move || event_loop(driver))
+        .unwrap();
+    (reactor, handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Wake;
+
+    use super::*;
+
+    struct Flag(AtomicBool);
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::Release);
+        }
+    }
+
+    #[test]
+    fn event_for_a_dropped_registration_does_not_wake_the_next_owner() {
+        let mut driver = new(16, true, false, Arc::new(Metrics::new(1)));
+        let reactor = driver.reactor().clone();
+
+        let mut old = mio::net::UdpSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let old_registration = reactor.register(&mut old, Interest::READABLE).unwrap();
+        let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender.send_to(b"ping", old.local_addr().unwrap()).unwrap();
 
-    REACTOR.set(reactor).ok().expect("Reactor already running");
-    spawn(move || event_loop(poll, wakers));
+        // The event is out of the OS poller, but not handled yet
+        while driver.events.is_empty() {
+            driver
+                .poll
+                .poll(&mut driver.events, Some(Duration::from_secs(1)))
+                .unwrap();
+        }
+        drop(old_registration);
+
+        let mut new = mio::net::UdpSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let new_registration = reactor.register(&mut new, Interest::READABLE).unwrap();
+        let woken = Arc::new(Flag(AtomicBool::new(false)));
+        new_registration.set_waker(&Context::from_waker(&woken.clone().into()));
+
+        driver.dispatch_events();
+        assert!(!woken.0.load(Ordering::Acquire));
+    }
 }
@@ -0,0 +1,151 @@
+/// The low bits of a key are the slot index plus one, so no key is ever 0,
+/// which is the token of the reactor's `mio::Waker`.
+const INDEX_BITS: u32 = 24;
+const INDEX_MASK: usize = (1 << INDEX_BITS) - 1;
+// What's left over counts how many times a slot has been reused
+const GENERATION_MASK: usize = usize::MAX >> INDEX_BITS;
+
+/// Stores values under keys that are reused once the value is removed.
+///
+/// A key carries the generation of its slot, which is bumped every time the
+/// slot is emptied. So a key held on to after its value was removed, say the
+/// token of an event that was already queued when a source was dropped,
+/// doesn't find whatever was inserted into the slot next.
+pub(crate) struct Slab<T> {
+    slots: Vec<Slot<T>>,
+    // Empty slots, reused before we grow
+    free: Vec<usize>,
+    len: usize,
+}
+
+struct Slot<T> {
+    generation: usize,
+    value: Option<T>,
+}
+
+impl<T> Slab<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// # Panics
+    /// If there are more than 2^24 - 1 values at once.
+    pub(crate) fn insert(&mut self, value: T) -> usize {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                assert!(self.slots.len() < INDEX_MASK, "too many registrations");
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: None,
+                });
+                self.slots.len() - 1
+            }
+        };
+
+        let slot = &mut self.slots[index];
+        slot.value = Some(value);
+        self.len += 1;
+        (slot.generation << INDEX_BITS) | (index + 1)
+    }
+
+    /// `None` if `key` was removed, even if its slot has been reused since.
+    pub(crate) fn get(&self, key: usize) -> Option<&T> {
+        let (index, generation) = decode(key)?;
+        let slot = self.slots.get(index)?;
+        if slot.generation != generation {
+            return None;
+        }
+        slot.value.as_ref()
+    }
+
+    pub(crate) fn remove(&mut self, key: usize) -> Option<T> {
+        let (index, generation) = decode(key)?;
+        let slot = self.slots.get_mut(index)?;
+        if slot.generation != generation {
+            return None;
+        }
+
+        let value = slot.value.take()?;
+        slot.generation = (slot.generation + 1) & GENERATION_MASK;
+        self.free.push(index);
+        self.len -= 1;
+        Some(value)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+}
+
+fn decode(key: usize) -> Option<(usize, usize)> {
+    let index = (key & INDEX_MASK).checked_sub(1)?;
+    Some((index, key >> INDEX_BITS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_key_is_rejected_after_its_slot_is_reused() {
+        let mut slab = Slab::new();
+        let old = slab.insert("old");
+        assert_eq!(slab.remove(old), Some("old"));
+
+        let new = slab.insert("new");
+        assert_eq!(new & INDEX_MASK, old & INDEX_MASK);
+        assert_ne!(new, old);
+        assert_eq!(slab.get(old), None);
+        assert_eq!(slab.remove(old), None);
+        assert_eq!(slab.get(new), Some(&"new"));
+    }
+
+    #[test]
+    fn removed_slots_are_reused_before_growing() {
+        let mut slab = Slab::new();
+        let keys: Vec<_> = (0..3).map(|i| slab.insert(i)).collect();
+        slab.remove(keys[1]);
+        slab.insert(3);
+        assert_eq!(slab.slots.len(), 3);
+        assert!(keys.iter().all(|&key| key != 0));
+    }
+
+    #[test]
+    fn len_counts_values_not_slots() {
+        let mut slab = Slab::new();
+        let a = slab.insert('a');
+        let b = slab.insert('b');
+        assert_eq!(slab.len(), 2);
+
+        slab.remove(a);
+        // Removing twice, or with a stale key, doesn't count
+        slab.remove(a);
+        assert_eq!(slab.len(), 1);
+
+        slab.insert('c');
+        slab.remove(b);
+        assert_eq!(slab.len(), 1);
+    }
+
+    #[test]
+    fn generation_wraps_around() {
+        let mut slab = Slab::new();
+        let first = slab.insert(0);
+        slab.remove(first);
+        slab.slots[0].generation = GENERATION_MASK;
+
+        let last = slab.insert(1);
+        assert_eq!(last >> INDEX_BITS, GENERATION_MASK);
+        assert_eq!(slab.remove(last), Some(1));
+        assert_eq!(slab.slots[0].generation, 0);
+
+        let wrapped = slab.insert(2);
+        assert_eq!(slab.get(last), None);
+        assert_eq!(slab.get(wrapped), Some(&2));
+    }
+}
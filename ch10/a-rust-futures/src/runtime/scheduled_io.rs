@@ -0,0 +1,218 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+
+use mio::event::Event;
+
+const READABLE: usize = 0b0001;
+const WRITABLE: usize = 0b0010;
+const READ_CLOSED: usize = 0b0100;
+const WRITE_CLOSED: usize = 0b1000;
+const READINESS_MASK: usize = 0b1111;
+// Everything above the readiness bits counts the events we've seen
+const TICK_SHIFT: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Read,
+    Write,
+}
+
+impl Direction {
+    /// The bits that mean an operation in this direction won't block.
+    fn mask(self) -> usize {
+        match self {
+            Direction::Read => READABLE | READ_CLOSED,
+            Direction::Write => WRITABLE | WRITE_CLOSED,
+        }
+    }
+}
+
+/// Returned by `ScheduledIo::poll_ready`. Hand it back to `clear_readiness`
+/// if the operation would block after all.
+#[derive(Clone, Copy)]
+pub(crate) struct ReadyEvent {
+    tick: usize,
+    direction: Direction,
+}
+
+/// What the reactor knows about one registered source: which directions
+/// are ready according to the events it has seen, and who to wake when that
+/// changes. Reading and writing have their own waker, so one task can wait
+/// to read while another waits to write to the same socket.
+#[derive(Default)]
+pub(crate) struct ScheduledIo {
+    readiness: AtomicUsize,
+    reader: Mutex<Option<Waker>>,
+    writer: Mutex<Option<Waker>>,
+}
+
+impl ScheduledIo {
+    fn waker_slot(&self, direction: Direction) -> &Mutex<Option<Waker>> {
+        match direction {
+            Direction::Read => &self.reader,
+            Direction::Write => &self.writer,
+        }
+    }
+
+    /// Called by the reactor for every event on this source. Only wakes the
+    /// side the event is about. Returns how many wakers were woken.
+    pub(crate) fn set_readiness(&self, event: &Event) -> usize {
+        let mut ready = 0;
+        if event.is_readable() {
+            ready |= READABLE;
+        }
+        if event.is_writable() {
+            ready |= WRITABLE;
+        }
+        if event.is_read_closed() {
+            ready |= READ_CLOSED;
+        }
+        if event.is_write_closed() {
+            ready |= WRITE_CLOSED;
+        }
+        // Let both sides find out about the error by trying
+        if event.is_error() {
+            ready |= READ_CLOSED | WRITE_CLOSED;
+        }
+        self.set_ready(ready)
+    }
+
+    /// Sets the `ready` bits, counts a new event and wakes the directions
+    /// that became ready.
+    fn set_ready(&self, ready: usize) -> usize {
+        let _ = self
+            .readiness
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+                let tick = (current >> TICK_SHIFT).wrapping_add(1);
+                Some((tick << TICK_SHIFT) | (current & READINESS_MASK) | ready)
+            });
+
+        [Direction::Read, Direction::Write]
+            .into_iter()
+            .filter(|direction| ready & direction.mask() != 0 && self.wake(*direction))
+            .count()
+    }
+
+    /// Stores `waker` for both directions, for `Registration::set_waker`
+    /// callers that don't care which way the event goes. Like the wakers
+    /// stored by `poll_ready`, it's taken when an event comes in and woken
+    /// only once, so callers have to check for readiness again after storing
+    /// it, or an event in between is missed.
+    pub(crate) fn set_waker(&self, waker: &Waker) {
+        for direction in [Direction::Read, Direction::Write] {
+            store_waker(self.waker_slot(direction), waker);
+        }
+    }
+
+    fn wake(&self, direction: Direction) -> bool {
+        let waker = self.waker_slot(direction).lock().unwrap().take();
+        waker.map(Waker::wake).is_some()
+    }
+
+    /// Ready if we've seen an event saying `direction` won't block.
+    /// Otherwise the task is woken when we do.
+    pub(crate) fn poll_ready(&self, direction: Direction, cx: &mut Context) -> Poll<ReadyEvent> {
+        if let Some(event) = self.ready_event(direction) {
+            return Poll::Ready(event);
+        }
+
+        store_waker(self.waker_slot(direction), cx.waker());
+
+        // The event might have come in before we stored the waker
+        match self.ready_event(direction) {
+            Some(event) => Poll::Ready(event),
+            None => Poll::Pending,
+        }
+    }
+
+    fn ready_event(&self, direction: Direction) -> Option<ReadyEvent> {
+        let current = self.readiness.load(Ordering::Acquire);
+        (current & direction.mask() != 0).then_some(ReadyEvent {
+            tick: current >> TICK_SHIFT,
+            direction,
+        })
+    }
+
+    /// The operation returned `WouldBlock`, so the source isn't ready in that
+    /// direction after all. Does nothing if a new event came in since
+    /// `event` was handed out, since that one might be about new data.
+    pub(crate) fn clear_readiness(&self, event: ReadyEvent) {
+        // Closed stays closed
+        let bits = match event.direction {
+            Direction::Read => READABLE,
+            Direction::Write => WRITABLE,
+        };
+
+        let _ = self
+            .readiness
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+                (current >> TICK_SHIFT == event.tick).then_some(current & !bits)
+            });
+    }
+}
+
+fn store_waker(slot: &Mutex<Option<Waker>>, waker: &Waker) {
+    let mut slot = slot.lock().unwrap();
+    match *slot {
+        Some(ref w) if w.will_wake(waker) => (),
+        _ => *slot = Some(waker.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll_ready(io: &ScheduledIo, direction: Direction) -> Poll<ReadyEvent> {
+        io.poll_ready(direction, &mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn clear_readiness_forgets_the_event_it_was_given() {
+        let io = ScheduledIo::default();
+        io.set_ready(READABLE | WRITABLE);
+
+        let Poll::Ready(event) = poll_ready(&io, Direction::Read) else {
+            panic!("not readable");
+        };
+        io.clear_readiness(event);
+        assert!(poll_ready(&io, Direction::Read).is_pending());
+        assert!(poll_ready(&io, Direction::Write).is_ready());
+    }
+
+    #[test]
+    fn clear_readiness_keeps_readiness_that_came_in_later() {
+        let io = ScheduledIo::default();
+        io.set_ready(READABLE);
+        let Poll::Ready(stale) = poll_ready(&io, Direction::Read) else {
+            panic!("not readable");
+        };
+
+        // More data arrived after the read that's about to return
+        // `WouldBlock` was attempted
+        io.set_ready(READABLE);
+        io.clear_readiness(stale);
+        let Poll::Ready(event) = poll_ready(&io, Direction::Read) else {
+            panic!("readiness from the newer event was cleared");
+        };
+
+        io.clear_readiness(event);
+        assert!(poll_ready(&io, Direction::Read).is_pending());
+    }
+
+    #[test]
+    fn closed_stays_ready() {
+        let io = ScheduledIo::default();
+        io.set_ready(READABLE | READ_CLOSED);
+        let Poll::Ready(event) = poll_ready(&io, Direction::Read) else {
+            panic!("not readable");
+        };
+        io.clear_readiness(event);
+        assert!(poll_ready(&io, Direction::Read).is_ready());
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+};
+
+/// How many times in a row a task can be taken from the LIFO slot before we
+/// go to the FIFO queue instead. Without this cap, a task that keeps waking
+/// itself would never let anyone else run. Tokio uses the same value.
+const MAX_LIFO_POLLS_IN_A_ROW: usize = 3;
+
+/// The order in which woken tasks are polled by the `Executor`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Scheduling {
+    /// Tasks are polled in the order they were woken.
+    #[default]
+    Fifo,
+    /// The most recently woken task is polled next (it's probably got its
+    /// data in the CPU cache), everything else is FIFO. A task that's pushed
+    /// out of the slot goes to the back of the queue.
+    LifoSlot,
+    /// Tasks spawned with a higher priority are always polled first. Tasks
+    /// with the same priority are polled in FIFO order.
+    Priority,
+}
+
+/// The queue of task ids that are ready to be polled. Only the executor
+/// touches it: it pushes spawned tasks and the ids it drains from the
+/// `WakeQueue` the wakers push to, and pops the next task to poll.
+pub(crate) struct ReadyQueue {
+    kind: Kind,
+}
+
+enum Kind {
+    Fifo(VecDeque<usize>),
+    LifoSlot {
+        slot: Option<usize>,
+        queue: VecDeque<usize>,
+        lifo_polls: usize,
+    },
+    Priority {
+        // (priority, insertion order, id), highest priority and then
+        // lowest insertion order pops first
+        heap: BinaryHeap<(u8, Reverse<u64>, usize)>,
+        priorities: HashMap<usize, u8>,
+        seq: u64,
+    },
+}
+
+impl ReadyQueue {
+    pub(crate) fn new(scheduling: Scheduling) -> Self {
+        let kind = match scheduling {
+            Scheduling::Fifo => Kind::Fifo(VecDeque::new()),
+            Scheduling::LifoSlot => Kind::LifoSlot {
+                slot: None,
+                queue: VecDeque::new(),
+                lifo_polls: 0,
+            },
+            Scheduling::Priority => Kind::Priority {
+                heap: BinaryHeap::new(),
+                priorities: HashMap::new(),
+                seq: 0,
+            },
+        };
+
+        Self { kind }
+    }
+
+    pub(crate) fn scheduling(&self) -> Scheduling {
+        match self.kind {
+            Kind::Fifo(_) => Scheduling::Fifo,
+            Kind::LifoSlot { .. } => Scheduling::LifoSlot,
+            Kind::Priority { .. } => Scheduling::Priority,
+        }
+    }
+
+    /// Only used by `Scheduling::Priority`. Tasks without a priority get `0`.
+    pub(crate) fn set_priority(&mut self, id: usize, priority: u8) {
+        if let Kind::Priority { priorities, .. } = &mut self.kind {
+            priorities.insert(id, priority);
+        }
+    }
+
+    /// Forget about a task that has finished.
+    pub(crate) fn remove(&mut self, id: usize) {
+        if let Kind::Priority { priorities, .. } = &mut self.kind {
+            priorities.remove(&id);
+        }
+    }
+
+    pub(crate) fn push(&mut self, id: usize) {
+        match &mut self.kind {
+            Kind::Fifo(queue) => queue.push_back(id),
+            Kind::LifoSlot { slot, queue, .. } => {
+                if let Some(prev) = slot.replace(id) {
+                    queue.push_back(prev);
+                }
+            }
+            Kind::Priority {
+                heap,
+                priorities,
+                seq,
+            } => {
+                let priority = priorities.get(&id).copied().unwrap_or_default();
+                heap.push((priority, Reverse(*seq), id));
+                *seq += 1;
+            }
+        }
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<usize> {
+        match &mut self.kind {
+            Kind::Fifo(queue) => queue.pop_front(),
+            Kind::LifoSlot {
+                slot,
+                queue,
+                lifo_polls,
+            } => {
+                if *lifo_polls >= MAX_LIFO_POLLS_IN_A_ROW {
+                    // Give the rest of the queue a chance
+                    if let Some(id) = slot.take() {
+                        queue.push_back(id);
+                    }
+                }
+
+                match slot.take() {
+                    Some(id) => {
+                        *lifo_polls += 1;
+                        Some(id)
+                    }
+                    None => {
+                        *lifo_polls = 0;
+                        queue.pop_front()
+                    }
+                }
+            }
+            Kind::Priority { heap, .. } => heap.pop().map(|(_, _, id)| id),
+        }
+    }
+}
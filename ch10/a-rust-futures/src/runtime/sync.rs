@@ -0,0 +1,25 @@
+//! Channels and locks for tasks, that don't block the thread they run on
+//! while they wait. Waiting is done by storing the `Waker` of the task and returning
+//! `Poll::Pending`, and whoever makes progress possible wakes it.
+
+pub mod broadcast;
+pub mod mpsc;
+pub mod oneshot;
+
+pub use mutex::{Mutex, MutexGuard};
+pub use notify::Notify;
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use semaphore::{Semaphore, SemaphorePermit};
+
+mod mutex;
+mod notify;
+mod rwlock;
+mod semaphore;
+
+use std::sync;
+
+/// Nothing here panics while holding a lock, but a panicking task shouldn't
+/// turn every other user of the channel or lock into a panicking task too.
+fn lock<T>(mutex: &sync::Mutex<T>) -> sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
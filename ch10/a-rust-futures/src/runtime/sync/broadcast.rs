@@ -0,0 +1,263 @@
+//! A multi-producer, multi-consumer channel where every receiver gets a
+//! clone of every value.
+//!
+//! The channel keeps the last `capacity` values. A receiver that falls
+//! further behind than that misses the oldest values and is told how many
+//! it missed with `RecvError::Lagged`.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt, future,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use super::lock;
+
+/// Creates a channel that keeps the last `capacity` values around for
+/// receivers that haven't seen them yet. More receivers are created with
+/// `Sender::subscribe`.
+///
+/// # Panics
+/// If `capacity` is zero.
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "`capacity` must be non-zero");
+    let shared = Arc::new(Mutex::new(State {
+        buffer: VecDeque::with_capacity(capacity),
+        capacity,
+        next_pos: 0,
+        senders: 1,
+        receivers: 0,
+        next_receiver: 0,
+        rx_wakers: HashMap::new(),
+    }));
+
+    let rx = Receiver::new(&shared);
+    (Sender { shared }, rx)
+}
+
+struct State<T> {
+    // The last `capacity` values and their position in the stream
+    buffer: VecDeque<(u64, T)>,
+    capacity: usize,
+    // The position the next value gets
+    next_pos: u64,
+    senders: usize,
+    receivers: usize,
+    next_receiver: u64,
+    // Receivers waiting for a new value
+    rx_wakers: HashMap<u64, Waker>,
+}
+
+impl<T> State<T> {
+    fn oldest_pos(&self) -> u64 {
+        self.buffer.front().map_or(self.next_pos, |(pos, _)| *pos)
+    }
+}
+
+pub struct Sender<T> {
+    shared: Arc<Mutex<State<T>>>,
+}
+
+impl<T: Clone> Sender<T> {
+    /// Sends `value` to every receiver and returns how many there are. Gives
+    /// the value back if there are none.
+    pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
+        let (receivers, wakers) = {
+            let mut state = lock(&self.shared);
+            if state.receivers == 0 {
+                return Err(SendError(value));
+            }
+
+            let pos = state.next_pos;
+            state.next_pos += 1;
+            if state.buffer.len() == state.capacity {
+                state.buffer.pop_front();
+            }
+            state.buffer.push_back((pos, value));
+            let wakers: Vec<_> = state.rx_wakers.drain().map(|(_, w)| w).collect();
+            (state.receivers, wakers)
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+        Ok(receivers)
+    }
+
+    /// A new receiver that gets every value sent from now on.
+    pub fn subscribe(&self) -> Receiver<T> {
+        Receiver::new(&self.shared)
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        lock(&self.shared).receivers
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        lock(&self.shared).senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let wakers: Vec<_> = {
+            let mut state = lock(&self.shared);
+            state.senders -= 1;
+            if state.senders > 0 {
+                return;
+            }
+            state.rx_wakers.drain().map(|(_, w)| w).collect()
+        };
+
+        // So they can see that the channel is closed
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    shared: Arc<Mutex<State<T>>>,
+    id: u64,
+    // The position of the next value we want
+    next: u64,
+}
+
+impl<T> Receiver<T> {
+    fn new(shared: &Arc<Mutex<State<T>>>) -> Self {
+        let mut state = lock(shared);
+        state.receivers += 1;
+        let id = state.next_receiver;
+        state.next_receiver += 1;
+        Self {
+            shared: shared.clone(),
+            id,
+            next: state.next_pos,
+        }
+    }
+}
+
+impl<T: Clone> Receiver<T> {
+    /// Waits for the next value. If we fell behind and missed some values,
+    /// returns `RecvError::Lagged` once and then continues with the oldest
+    /// value still in the channel.
+    pub async fn recv(&mut self) -> Result<T, RecvError> {
+        future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    pub fn poll_recv(&mut self, cx: &mut Context) -> Poll<Result<T, RecvError>> {
+        match self.try_recv() {
+            Ok(value) => Poll::Ready(Ok(value)),
+            Err(TryRecvError::Lagged(n)) => Poll::Ready(Err(RecvError::Lagged(n))),
+            Err(TryRecvError::Closed) => Poll::Ready(Err(RecvError::Closed)),
+            Err(TryRecvError::Empty) => {
+                let mut state = lock(&self.shared);
+                // A value might have been sent after `try_recv` let go of
+                // the lock
+                if state.next_pos != self.next || state.senders == 0 {
+                    cx.waker().wake_by_ref();
+                } else {
+                    state.rx_wakers.insert(self.id, cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Returns the next value if there is one, without waiting.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let state = lock(&self.shared);
+        let oldest = state.oldest_pos();
+        if self.next < oldest {
+            let missed = oldest - self.next;
+            self.next = oldest;
+            return Err(TryRecvError::Lagged(missed));
+        }
+
+        if self.next == state.next_pos {
+            return Err(if state.senders == 0 {
+                TryRecvError::Closed
+            } else {
+                TryRecvError::Empty
+            });
+        }
+
+        let (_, value) = &state.buffer[(self.next - oldest) as usize];
+        self.next += 1;
+        Ok(value.clone())
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = lock(&self.shared);
+        state.receivers -= 1;
+        state.rx_wakers.remove(&self.id);
+    }
+}
+
+/// There are no receivers. Holds the value that couldn't be sent.
+#[derive(PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "channel closed")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RecvError {
+    /// Every sender is gone and we've seen every value.
+    Closed,
+    /// We fell behind and missed this many values.
+    Lagged(u64),
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvError::Closed => write!(f, "channel closed"),
+            RecvError::Lagged(n) => write!(f, "receiver lagged behind by {n} values"),
+        }
+    }
+}
+
+impl Error for RecvError {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// No new values right now.
+    Empty,
+    /// Every sender is gone and we've seen every value.
+    Closed,
+    /// We fell behind and missed this many values.
+    Lagged(u64),
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "channel empty"),
+            TryRecvError::Closed => write!(f, "channel closed"),
+            TryRecvError::Lagged(n) => write!(f, "receiver lagged behind by {n} values"),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
@@ -0,0 +1,480 @@
+//! Multi-producer, single-consumer queues, bounded and unbounded.
+//!
+//! With a bounded channel, `send` waits while the queue is full, so a fast
+//! producer can't run away from a slow consumer. Waiting senders are served
+//! in the order they started waiting.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    error::Error,
+    fmt,
+    future::{self, Future},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use super::lock;
+
+/// Creates a channel that holds at most `capacity` values.
+///
+/// # Panics
+/// If `capacity` is zero.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "`capacity` must be non-zero");
+    let chan = Chan::new(Some(capacity));
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+/// Creates a channel without a limit on how many values it holds.
+pub fn unbounded_channel<T>() -> (UnboundedSender<T>, UnboundedReceiver<T>) {
+    let chan = Chan::new(None);
+    (
+        UnboundedSender { chan: chan.clone() },
+        UnboundedReceiver {
+            inner: Receiver { chan },
+        },
+    )
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    // `None` for unbounded channels
+    capacity: Option<usize>,
+    senders: usize,
+    rx_closed: bool,
+    rx_waker: Option<Waker>,
+    // Senders waiting for room in the queue, oldest first
+    send_waiters: VecDeque<(u64, Waker)>,
+    // Waiting senders that were given a slot but haven't been polled since
+    granted: HashSet<u64>,
+    next_waiter: u64,
+}
+
+impl<T> State<T> {
+    /// Slots given to waiting senders count as taken, so a sender that
+    /// comes along later can't grab them first.
+    fn is_full(&self) -> bool {
+        self.capacity
+            .is_some_and(|cap| self.queue.len() + self.granted.len() >= cap)
+    }
+
+    fn push(&mut self, value: T) -> Option<Waker> {
+        self.queue.push_back(value);
+        self.rx_waker.take()
+    }
+
+    /// Gives a free slot to the oldest waiting sender, if there is one.
+    /// Returns its waker to call once the lock is released.
+    fn next_send_waiter(&mut self) -> Option<Waker> {
+        if self.is_full() {
+            return None;
+        }
+        let (id, waker) = self.send_waiters.pop_front()?;
+        self.granted.insert(id);
+        Some(waker)
+    }
+}
+
+struct Chan<T> {
+    state: Mutex<State<T>>,
+}
+
+impl<T> Chan<T> {
+    fn new(capacity: Option<usize>) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                capacity,
+                senders: 1,
+                rx_closed: false,
+                rx_waker: None,
+                send_waiters: VecDeque::new(),
+                granted: HashSet::new(),
+                next_waiter: 0,
+            }),
+        })
+    }
+
+    fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let waker = {
+            let mut state = lock(&self.state);
+            if state.rx_closed {
+                return Err(TrySendError::Closed(value));
+            }
+            // Taking a slot while others wait would let us jump the line
+            if state.is_full() || !state.send_waiters.is_empty() {
+                return Err(TrySendError::Full(value));
+            }
+            state.push(value)
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    fn poll_recv(&self, cx: &mut Context) -> Poll<Option<T>> {
+        let (value, waker) = {
+            let mut state = lock(&self.state);
+            match state.queue.pop_front() {
+                // There's room for one more value now
+                Some(value) => (value, state.next_send_waiter()),
+                None if state.senders == 0 || state.rx_closed => return Poll::Ready(None),
+                None => {
+                    match state.rx_waker {
+                        Some(ref w) if w.will_wake(cx.waker()) => (),
+                        _ => state.rx_waker = Some(cx.waker().clone()),
+                    }
+                    return Poll::Pending;
+                }
+            }
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Poll::Ready(Some(value))
+    }
+
+    fn close(&self) {
+        let waiters = {
+            let mut state = lock(&self.state);
+            state.rx_closed = true;
+            std::mem::take(&mut state.send_waiters)
+        };
+
+        // They'll see that the channel is closed and give up
+        for (_, waker) in waiters {
+            waker.wake();
+        }
+    }
+
+    fn add_sender(&self) {
+        lock(&self.state).senders += 1;
+    }
+
+    fn drop_sender(&self) {
+        let waker = {
+            let mut state = lock(&self.state);
+            state.senders -= 1;
+            if state.senders > 0 {
+                return;
+            }
+            state.rx_waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// The sending half of a bounded channel. Can be cloned to get more
+/// producers.
+pub struct Sender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends `value`, waiting for room in the queue if it's full. Gives the
+    /// value back if the receiver is gone.
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        SendFuture {
+            chan: &self.chan,
+            value: Some(value),
+            waiter: None,
+        }
+        .await
+    }
+
+    /// Sends `value` if there's room in the queue right now.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.chan.try_send(value)
+    }
+
+    /// Returns `true` if the receiver has been dropped or closed.
+    pub fn is_closed(&self) -> bool {
+        lock(&self.chan.state).rx_closed
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.add_sender();
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.chan.drop_sender();
+    }
+}
+
+struct SendFuture<'a, T> {
+    chan: &'a Chan<T>,
+    value: Option<T>,
+    // Our place in `send_waiters`, if we're waiting
+    waiter: Option<u64>,
+}
+
+impl<T> Unpin for SendFuture<'_, T> {}
+
+impl<T> Future for SendFuture<'_, T> {
+    type Output = Result<(), SendError<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let value = this
+            .value
+            .take()
+            .expect("SendFuture polled after completion");
+
+        let waker = {
+            let mut state = lock(&this.chan.state);
+            if state.rx_closed {
+                if let Some(id) = this.waiter.take() {
+                    state.granted.remove(&id);
+                }
+                return Poll::Ready(Err(SendError(value)));
+            }
+
+            match this.waiter {
+                // Woken with a slot set aside for us
+                Some(id) if state.granted.remove(&id) => this.waiter = None,
+                Some(id) => {
+                    if let Some((_, w)) = state.send_waiters.iter_mut().find(|(w, _)| *w == id) {
+                        w.clone_from(cx.waker());
+                    }
+                    this.value = Some(value);
+                    return Poll::Pending;
+                }
+                None if state.is_full() || !state.send_waiters.is_empty() => {
+                    let id = state.next_waiter;
+                    state.next_waiter += 1;
+                    state.send_waiters.push_back((id, cx.waker().clone()));
+                    this.waiter = Some(id);
+                    this.value = Some(value);
+                    return Poll::Pending;
+                }
+                None => (),
+            }
+            state.push(value)
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> Drop for SendFuture<'_, T> {
+    fn drop(&mut self) {
+        let Some(id) = self.waiter else {
+            return;
+        };
+
+        let waker = {
+            let mut state = lock(&self.chan.state);
+            if state.granted.remove(&id) {
+                // We were given a slot we're not going to use, so pass it on
+                // to the next sender in line
+                state.next_send_waiter()
+            } else {
+                state.send_waiters.retain(|(w, _)| *w != id);
+                None
+            }
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// The receiving half of a bounded channel.
+pub struct Receiver<T> {
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Waits for the next value. Returns `None` once every sender is gone
+    /// and the queue is empty.
+    pub async fn recv(&mut self) -> Option<T> {
+        future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    pub fn poll_recv(&mut self, cx: &mut Context) -> Poll<Option<T>> {
+        self.chan.poll_recv(cx)
+    }
+
+    /// Returns the next value if there is one, without waiting.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let (value, waker) = {
+            let mut state = lock(&self.chan.state);
+            match state.queue.pop_front() {
+                Some(value) => (value, state.next_send_waiter()),
+                None if state.senders == 0 || state.rx_closed => return Err(TryRecvError::Closed),
+                None => return Err(TryRecvError::Empty),
+            }
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Ok(value)
+    }
+
+    /// Makes every `send` fail from now on. Values already in the queue can
+    /// still be received.
+    pub fn close(&mut self) {
+        self.chan.close();
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.chan.close();
+        // Drop the values nobody is going to receive now rather than when
+        // the last sender goes away
+        let queue = std::mem::take(&mut lock(&self.chan.state).queue);
+        drop(queue);
+    }
+}
+
+/// The sending half of an unbounded channel. Sending never waits.
+pub struct UnboundedSender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> UnboundedSender<T> {
+    /// Sends `value`. Gives it back if the receiver is gone.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.chan.try_send(value).map_err(|e| match e {
+            TrySendError::Closed(value) | TrySendError::Full(value) => SendError(value),
+        })
+    }
+
+    /// Returns `true` if the receiver has been dropped or closed.
+    pub fn is_closed(&self) -> bool {
+        lock(&self.chan.state).rx_closed
+    }
+}
+
+impl<T> Clone for UnboundedSender<T> {
+    fn clone(&self) -> Self {
+        self.chan.add_sender();
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for UnboundedSender<T> {
+    fn drop(&mut self) {
+        self.chan.drop_sender();
+    }
+}
+
+/// The receiving half of an unbounded channel.
+pub struct UnboundedReceiver<T> {
+    inner: Receiver<T>,
+}
+
+impl<T> UnboundedReceiver<T> {
+    /// Waits for the next value. Returns `None` once every sender is gone
+    /// and the queue is empty.
+    pub async fn recv(&mut self) -> Option<T> {
+        self.inner.recv().await
+    }
+
+    pub fn poll_recv(&mut self, cx: &mut Context) -> Poll<Option<T>> {
+        self.inner.poll_recv(cx)
+    }
+
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.inner.try_recv()
+    }
+
+    pub fn close(&mut self) {
+        self.inner.close();
+    }
+}
+
+/// The receiver is gone. Holds the value that couldn't be sent.
+#[derive(PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "channel closed")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+#[derive(PartialEq, Eq)]
+pub enum TrySendError<T> {
+    /// The queue is full.
+    Full(T),
+    /// The receiver is gone.
+    Closed(T),
+}
+
+impl<T> TrySendError<T> {
+    pub fn into_inner(self) -> T {
+        match self {
+            TrySendError::Full(value) | TrySendError::Closed(value) => value,
+        }
+    }
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => write!(f, "Full(..)"),
+            TrySendError::Closed(_) => write!(f, "Closed(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => write!(f, "channel full"),
+            TrySendError::Closed(_) => write!(f, "channel closed"),
+        }
+    }
+}
+
+impl<T> Error for TrySendError<T> {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// Nothing to receive right now.
+    Empty,
+    /// Every sender is gone and the queue is empty.
+    Closed,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "channel empty"),
+            TryRecvError::Closed => write!(f, "channel closed"),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
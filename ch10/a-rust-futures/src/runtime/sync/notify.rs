@@ -0,0 +1,149 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll, Waker},
+};
+
+use super::lock;
+
+/// Wakes up waiting tasks without sending them any data.
+///
+/// `notify_one` wakes the task that has been waiting the longest. If nobody
+/// is waiting, the notification is stored and the next call to `notified`
+/// completes right away. `notify_waiters` wakes every task that's waiting
+/// right now and doesn't store anything.
+pub struct Notify {
+    state: Mutex<State>,
+}
+
+struct State {
+    // Set by `notify_one` when nobody was waiting
+    permit: bool,
+    waiters: VecDeque<(u64, Waker)>,
+    // Waiters that were notified but haven't been polled since. The value
+    // says whether it was `notify_one`, which has to be passed on if the
+    // waiter goes away without seeing it.
+    notified: HashMap<u64, bool>,
+    next_id: u64,
+}
+
+impl Notify {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                permit: false,
+                waiters: VecDeque::new(),
+                notified: HashMap::new(),
+                next_id: 0,
+            }),
+        }
+    }
+
+    /// Completes once this task is notified. A task counts as waiting from
+    /// the first time the returned future is polled.
+    pub async fn notified(&self) {
+        Notified {
+            notify: self,
+            waiter: None,
+        }
+        .await
+    }
+
+    pub fn notify_one(&self) {
+        let waker = {
+            let mut state = lock(&self.state);
+            match state.waiters.pop_front() {
+                Some((id, waker)) => {
+                    state.notified.insert(id, true);
+                    waker
+                }
+                None => {
+                    state.permit = true;
+                    return;
+                }
+            }
+        };
+
+        waker.wake();
+    }
+
+    pub fn notify_waiters(&self) {
+        let waiters = {
+            let mut state = lock(&self.state);
+            let waiters = std::mem::take(&mut state.waiters);
+            for (id, _) in &waiters {
+                state.notified.insert(*id, false);
+            }
+            waiters
+        };
+
+        for (_, waker) in waiters {
+            waker.wake();
+        }
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Notified<'a> {
+    notify: &'a Notify,
+    waiter: Option<u64>,
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = lock(&self.notify.state);
+
+        let Some(id) = self.waiter else {
+            if state.permit {
+                state.permit = false;
+                return Poll::Ready(());
+            }
+
+            let id = state.next_id;
+            state.next_id += 1;
+            state.waiters.push_back((id, cx.waker().clone()));
+            drop(state);
+            self.waiter = Some(id);
+            return Poll::Pending;
+        };
+
+        if state.notified.remove(&id).is_some() {
+            drop(state);
+            self.waiter = None;
+            return Poll::Ready(());
+        }
+
+        if let Some((_, waker)) = state.waiters.iter_mut().find(|(w, _)| *w == id) {
+            waker.clone_from(cx.waker());
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        let Some(id) = self.waiter else {
+            return;
+        };
+
+        let was_notify_one = {
+            let mut state = lock(&self.notify.state);
+            state.waiters.retain(|(w, _)| *w != id);
+            state.notified.remove(&id) == Some(true)
+        };
+
+        // Don't lose a `notify_one` just because we stopped waiting
+        if was_notify_one {
+            self.notify.notify_one();
+        }
+    }
+}
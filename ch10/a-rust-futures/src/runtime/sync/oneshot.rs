@@ -0,0 +1,159 @@
+//! A channel for sending a single value from one task to another.
+
+use std::{
+    error::Error,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use super::lock;
+
+/// Creates a new oneshot channel. The `Receiver` is a future that completes
+/// with the value once it's sent.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let inner = Arc::new(Mutex::new(State {
+        value: None,
+        rx_waker: None,
+        tx_dropped: false,
+        rx_dropped: false,
+    }));
+
+    (
+        Sender {
+            inner: inner.clone(),
+        },
+        Receiver { inner },
+    )
+}
+
+struct State<T> {
+    value: Option<T>,
+    rx_waker: Option<Waker>,
+    tx_dropped: bool,
+    rx_dropped: bool,
+}
+
+pub struct Sender<T> {
+    inner: Arc<Mutex<State<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends `value` to the receiver. Gives it back if the receiver is gone.
+    pub fn send(self, value: T) -> Result<(), T> {
+        let waker = {
+            let mut state = lock(&self.inner);
+            if state.rx_dropped {
+                return Err(value);
+            }
+            state.value = Some(value);
+            state.rx_waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the receiver has been dropped or closed.
+    pub fn is_closed(&self) -> bool {
+        lock(&self.inner).rx_dropped
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut state = lock(&self.inner);
+            state.tx_dropped = true;
+            state.rx_waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    inner: Arc<Mutex<State<T>>>,
+}
+
+impl<T> Receiver<T> {
+    /// Returns the value if it has been sent, without waiting.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut state = lock(&self.inner);
+        match state.value.take() {
+            Some(value) => Ok(value),
+            None if state.tx_dropped => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Makes `send` fail from now on. A value that was already sent can still
+    /// be received.
+    pub fn close(&mut self) {
+        lock(&self.inner).rx_dropped = true;
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = lock(&self.inner);
+        if let Some(value) = state.value.take() {
+            return Poll::Ready(Ok(value));
+        }
+
+        if state.tx_dropped {
+            return Poll::Ready(Err(RecvError(())));
+        }
+
+        match state.rx_waker {
+            Some(ref w) if w.will_wake(cx.waker()) => (),
+            _ => state.rx_waker = Some(cx.waker().clone()),
+        }
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// The sender was dropped without sending a value.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RecvError(());
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "channel closed")
+    }
+}
+
+impl Error for RecvError {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// Nothing has been sent yet.
+    Empty,
+    /// The sender was dropped without sending a value.
+    Closed,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "channel empty"),
+            TryRecvError::Closed => write!(f, "channel closed"),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
@@ -0,0 +1,213 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    future::Future,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll, Waker},
+};
+
+use super::lock;
+
+/// Hands out a fixed number of permits. Tasks that ask for more permits than
+/// are available wait in line, and are served strictly in the order they
+/// asked: a task that wants many permits isn't overtaken by later tasks
+/// that want fewer. `Mutex` and `RwLock` are built on top of this.
+pub struct Semaphore {
+    state: Mutex<State>,
+}
+
+struct State {
+    permits: usize,
+    waiters: VecDeque<Waiter>,
+    // Waiters that were given their permits but haven't been polled since
+    granted: HashSet<u64>,
+    next_id: u64,
+}
+
+struct Waiter {
+    id: u64,
+    needed: usize,
+    waker: Waker,
+}
+
+impl State {
+    /// Gives permits to the waiters at the front of the line for as long as
+    /// there are enough of them. Returns the wakers to call once the lock is
+    /// released.
+    fn assign(&mut self) -> Vec<Waker> {
+        let mut wakers = vec![];
+        while let Some(waiter) = self.waiters.front() {
+            if waiter.needed > self.permits {
+                break;
+            }
+            let waiter = self.waiters.pop_front().unwrap();
+            self.permits -= waiter.needed;
+            self.granted.insert(waiter.id);
+            wakers.push(waiter.waker);
+        }
+        wakers
+    }
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        Self {
+            state: Mutex::new(State {
+                permits,
+                waiters: VecDeque::new(),
+                granted: HashSet::new(),
+                next_id: 0,
+            }),
+        }
+    }
+
+    pub fn available_permits(&self) -> usize {
+        lock(&self.state).permits
+    }
+
+    /// Waits for one permit. It's given back when the returned
+    /// `SemaphorePermit` is dropped.
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        self.acquire_many(1).await
+    }
+
+    /// Waits until `n` permits can be taken at once.
+    pub async fn acquire_many(&self, n: usize) -> SemaphorePermit<'_> {
+        Acquire {
+            semaphore: self,
+            needed: n,
+            waiter: None,
+        }
+        .await;
+
+        SemaphorePermit {
+            semaphore: self,
+            permits: n,
+        }
+    }
+
+    /// Takes a permit if one is available right now and nobody is waiting.
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        self.try_acquire_many(1)
+    }
+
+    pub fn try_acquire_many(&self, n: usize) -> Option<SemaphorePermit<'_>> {
+        let mut state = lock(&self.state);
+        // Taking permits while others wait would let us jump the line
+        if !state.waiters.is_empty() || state.permits < n {
+            return None;
+        }
+        state.permits -= n;
+        Some(SemaphorePermit {
+            semaphore: self,
+            permits: n,
+        })
+    }
+
+    /// Adds `n` permits and wakes the waiters that can now get theirs.
+    pub fn add_permits(&self, n: usize) {
+        let wakers = {
+            let mut state = lock(&self.state);
+            state.permits += n;
+            state.assign()
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+/// Holds on to permits until dropped.
+#[must_use]
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+impl SemaphorePermit<'_> {
+    /// Keeps the permits taken. They're gone from the semaphore for good.
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+
+    pub fn num_permits(&self) -> usize {
+        self.permits
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.semaphore.add_permits(self.permits);
+        }
+    }
+}
+
+struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    needed: usize,
+    // Our id once we're in line
+    waiter: Option<u64>,
+}
+
+impl Future for Acquire<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = lock(&self.semaphore.state);
+
+        let Some(id) = self.waiter else {
+            if state.waiters.is_empty() && state.permits >= self.needed {
+                state.permits -= self.needed;
+                return Poll::Ready(());
+            }
+
+            let id = state.next_id;
+            state.next_id += 1;
+            state.waiters.push_back(Waiter {
+                id,
+                needed: self.needed,
+                waker: cx.waker().clone(),
+            });
+            drop(state);
+            self.waiter = Some(id);
+            return Poll::Pending;
+        };
+
+        if state.granted.remove(&id) {
+            drop(state);
+            self.waiter = None;
+            return Poll::Ready(());
+        }
+
+        if let Some(waiter) = state.waiters.iter_mut().find(|w| w.id == id) {
+            waiter.waker.clone_from(cx.waker());
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        let Some(id) = self.waiter else {
+            return;
+        };
+
+        let wakers = {
+            let mut state = lock(&self.semaphore.state);
+            if state.granted.remove(&id) {
+                // We got the permits but nobody is going to use them
+                state.permits += self.needed;
+            } else {
+                state.waiters.retain(|w| w.id != id);
+            }
+            // Either way, the waiters behind us might be able to go now
+            state.assign()
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    any::Any,
+    error::Error,
+    fmt,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+/// Called with the panic payload when a spawned task panics. The task is
+/// removed from the executor either way, and its `JoinHandle` resolves to a
+/// `JoinError` holding the payload.
+pub type PanicHook = Arc<dyn Fn(&(dyn Any + Send)) + Send + Sync>;
+
+/// The reason a task didn't produce an output.
+pub struct JoinError {
+    repr: Repr,
+}
+
+enum Repr {
+    Cancelled,
+    Panic(Box<dyn Any + Send>),
+}
+
+impl JoinError {
+    fn cancelled() -> Self {
+        Self {
+            repr: Repr::Cancelled,
+        }
+    }
+
+    fn panic(payload: Box<dyn Any + Send>) -> Self {
+        Self {
+            repr: Repr::Panic(payload),
+        }
+    }
+
+    /// The task was aborted through its `JoinHandle` (or the executor
+    /// dropped it) before it completed.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self.repr, Repr::Cancelled)
+    }
+
+    /// The task panicked while being polled.
+    pub fn is_panic(&self) -> bool {
+        matches!(self.repr, Repr::Panic(_))
+    }
+
+    /// Returns the panic payload, so you can for example continue unwinding
+    /// with `std::panic::resume_unwind`.
+    ///
+    /// # Panics
+    /// If the task didn't panic.
+    pub fn into_panic(self) -> Box<dyn Any + Send> {
+        self.try_into_panic()
+            .expect("`JoinError` reason is not a panic")
+    }
+
+    pub fn try_into_panic(self) -> Result<Box<dyn Any + Send>, JoinError> {
+        match self.repr {
+            Repr::Panic(payload) => Ok(payload),
+            _ => Err(self),
+        }
+    }
+}
+
+/// Most panics carry either a `&str` or a `String`
+fn panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(|s| s.as_str()))
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.repr {
+            Repr::Cancelled => write!(f, "task was cancelled"),
+            Repr::Panic(p) => match panic_message(p.as_ref()) {
+                Some(msg) => write!(f, "task panicked with message {msg:?}"),
+                None => write!(f, "task panicked"),
+            },
+        }
+    }
+}
+
+impl fmt::Debug for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.repr {
+            Repr::Cancelled => write!(f, "JoinError::Cancelled"),
+            Repr::Panic(p) => match panic_message(p.as_ref()) {
+                Some(msg) => write!(f, "JoinError::Panic({msg:?})"),
+                None => write!(f, "JoinError::Panic(..)"),
+            },
+        }
+    }
+}
+
+impl Error for JoinError {}
+
+/// State shared between a spawned task and its `JoinHandle`.
+struct JoinState<T> {
+    output: Option<Result<T, JoinError>>,
+    finished: bool,
+    aborted: bool,
+    // The `JoinHandle` is gone, so nothing can abort the task any more
+    detached: bool,
+    // The task waiting on the `JoinHandle`
+    join_waker: Option<Waker>,
+    // The spawned task itself, so `abort` can get it polled one last time
+    task_waker: Option<Waker>,
+}
+
+type Shared<T> = Arc<Mutex<JoinState<T>>>;
+
+/// Lets the executor look at the wakers the runtime itself keeps in a
+/// `JoinState`, whatever the output type. Those don't count as someone who
+/// can wake a task, see `Executor::detect_lost_wakeups`.
+pub(crate) trait JoinWakers {
+    fn for_each_waker(&self, f: &mut dyn FnMut(&Waker));
+}
+
+impl<T> JoinWakers for Mutex<JoinState<T>> {
+    fn for_each_waker(&self, f: &mut dyn FnMut(&Waker)) {
+        let state = lock(self);
+        state.join_waker.iter().chain(&state.task_waker).for_each(f);
+    }
+}
+
+fn lock<T>(state: &Mutex<JoinState<T>>) -> MutexGuard<'_, JoinState<T>> {
+    // A task panicking never happens while the lock is held, but we don't
+    // want a poisoned lock to hide the original panic either.
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn complete<T>(state: &Shared<T>, output: Result<T, JoinError>) {
+    let waker = {
+        let mut state = lock(state);
+        state.output = Some(output);
+        state.finished = true;
+        state.task_waker = None;
+        state.join_waker.take()
+    };
+
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+/// Wraps the spawned future so its output ends up in the `JoinHandle`
+/// instead of being thrown away. This is what the executor stores in its
+/// task map.
+pub(crate) struct Harness<F: Future> {
+    future: Option<Pin<Box<F>>>,
+    state: Shared<F::Output>,
+    panic_hook: Option<PanicHook>,
+}
+
+pub(crate) fn new_task<F: Future>(
+    future: F,
+    panic_hook: Option<PanicHook>,
+) -> (Harness<F>, JoinHandle<F::Output>) {
+    let state = Arc::new(Mutex::new(JoinState {
+        output: None,
+        finished: false,
+        aborted: false,
+        detached: false,
+        join_waker: None,
+        task_waker: None,
+    }));
+
+    let harness = Harness {
+        future: Some(Box::pin(future)),
+        state: state.clone(),
+        panic_hook,
+    };
+
+    (harness, JoinHandle { state })
+}
+
+impl<F: Future> Harness<F>
+where
+    F::Output: 'static,
+{
+    pub(crate) fn join_wakers(&self) -> Arc<dyn JoinWakers> {
+        self.state.clone()
+    }
+}
+
+impl<F: Future> Future for Harness<F> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        {
+            let mut state = lock(&self.state);
+            if state.aborted {
+                drop(state);
+                // Drop the future before anyone is told the task is gone
+                self.future = None;
+                complete(&self.state, Err(JoinError::cancelled()));
+                return Poll::Ready(());
+            }
+
+            // Only keep the waker if someone can still abort us
+            match state.task_waker {
+                _ if state.detached => (),
+                Some(ref w) if w.will_wake(cx.waker()) => (),
+                _ => state.task_waker = Some(cx.waker().clone()),
+            }
+        }
+
+        let future = self
+            .future
+            .as_mut()
+            .expect("Harness polled after completion");
+
+        // A panic in one task shouldn't take down the executor and every
+        // other task with it. The future can't be used after it panicked, so
+        // we treat it as completed.
+        match panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+            Ok(Poll::Ready(output)) => {
+                self.future = None;
+                complete(&self.state, Ok(output));
+                Poll::Ready(())
+            }
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => {
+                self.future = None;
+                if let Some(hook) = &self.panic_hook {
+                    hook(payload.as_ref());
+                }
+                complete(&self.state, Err(JoinError::panic(payload)));
+                Poll::Ready(())
+            }
+        }
+    }
+}
+
+impl<F: Future> Drop for Harness<F> {
+    fn drop(&mut self) {
+        // If the future is still here the task never completed: either
+        // something else is unwinding and took the task with it, or the
+        // executor dropped the task.
+        if self.future.take().is_some() {
+            let err = if thread::panicking() {
+                JoinError::panic(Box::new("task was dropped during a panic"))
+            } else {
+                JoinError::cancelled()
+            };
+            complete(&self.state, Err(err));
+        }
+    }
+}
+
+/// Returned by `spawn`. Awaiting it gives you the output of the task, or a
+/// `JoinError` if the task panicked or was aborted.
+pub struct JoinHandle<T> {
+    state: Shared<T>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Cancels the task. The future is dropped the next time the executor
+    /// gets to it, and awaiting this handle returns a cancelled `JoinError`.
+    /// Does nothing if the task has already finished.
+    pub fn abort(&self) {
+        let waker = {
+            let mut state = lock(&self.state);
+            if state.finished {
+                return;
+            }
+            state.aborted = true;
+            state.task_waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Returns `true` if the task has completed, been aborted or panicked.
+    pub fn is_finished(&self) -> bool {
+        lock(&self.state).finished
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut state = lock(&self.state);
+            state.detached = true;
+            state.task_waker.take()
+        };
+        // Dropped outside the lock, it might be the last reference
+        drop(waker);
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = lock(&self.state);
+        if let Some(output) = state.output.take() {
+            return Poll::Ready(output);
+        }
+
+        if state.finished {
+            panic!("JoinHandle polled after completion");
+        }
+
+        state.join_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
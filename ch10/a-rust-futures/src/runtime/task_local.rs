@@ -0,0 +1,209 @@
+use std::{
+    cell::RefCell,
+    error::Error,
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    thread,
+};
+
+/// Declares task-local values. They're set for the duration of a future with
+/// `LocalKey::scope` and read with `LocalKey::with` or `LocalKey::get`.
+///
+/// ```
+/// a_rust_futures::task_local! {
+///     static REQUEST_ID: u64;
+/// }
+///
+/// let mut rt = a_rust_futures::runtime::init();
+/// rt.block_on(REQUEST_ID.scope(7, async {
+///     assert_eq!(REQUEST_ID.get(), 7);
+/// }));
+/// ```
+#[macro_export]
+macro_rules! task_local {
+    () => {};
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty; $($rest:tt)*) => {
+        $crate::__task_local_inner!($(#[$attr])* $vis $name, $t);
+        $crate::task_local!($($rest)*);
+    };
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty) => {
+        $crate::__task_local_inner!($(#[$attr])* $vis $name, $t);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __task_local_inner {
+    ($(#[$attr:meta])* $vis:vis $name:ident, $t:ty) => {
+        $(#[$attr])*
+        $vis static $name: $crate::runtime::LocalKey<$t> = {
+            ::std::thread_local! {
+                static __KEY: ::std::cell::RefCell<::std::option::Option<$t>> =
+                    const { ::std::cell::RefCell::new(::std::option::Option::None) };
+            }
+
+            $crate::runtime::LocalKey { inner: __KEY }
+        };
+    };
+}
+
+/// A key for a task-local value, created with `task_local!`.
+///
+/// The value lives in the future passed to `scope`. Right before that future
+/// is polled it's moved into a thread local, and it's moved back out right
+/// after, so two tasks on the same thread never see each other's value.
+/// The executor polls a task by polling its future, so for a task spawned
+/// as `spawn(KEY.scope(value, fut))` this is exactly "set before every poll
+/// of the task, removed after", on either executor.
+///
+/// A task spawned from inside `scope` doesn't inherit the value, since the
+/// executor polls it on its own and not from within the scoped future. Pass
+/// the value on with a `scope` of its own if the task needs it.
+pub struct LocalKey<T: 'static> {
+    #[doc(hidden)]
+    pub inner: thread::LocalKey<RefCell<Option<T>>>,
+}
+
+impl<T: 'static> LocalKey<T> {
+    /// Sets the value to `value` while `future` runs, including across
+    /// awaits. Scopes can be nested, the innermost value wins.
+    pub fn scope<F: Future>(&'static self, value: T, future: F) -> TaskLocalFuture<T, F> {
+        TaskLocalFuture {
+            local: self,
+            slot: Some(value),
+            future: Some(Box::pin(future)),
+        }
+    }
+
+    /// Sets the value to `value` while `f` runs.
+    pub fn sync_scope<F, R>(&'static self, value: T, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let mut slot = Some(value);
+        self.scope_inner(&mut slot, f)
+    }
+
+    /// Moves the value in `slot` into the thread local while `f` runs and
+    /// back into `slot` afterwards, even if `f` panics.
+    fn scope_inner<F, R>(&'static self, slot: &mut Option<T>, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        struct Guard<'a, T: 'static> {
+            local: &'static LocalKey<T>,
+            slot: &'a mut Option<T>,
+        }
+
+        impl<T: 'static> Drop for Guard<'_, T> {
+            fn drop(&mut self) {
+                self.local
+                    .inner
+                    .with(|c| std::mem::swap(self.slot, &mut *c.borrow_mut()));
+            }
+        }
+
+        self.inner
+            .with(|c| std::mem::swap(slot, &mut *c.borrow_mut()));
+        let _guard = Guard { local: self, slot };
+        f()
+    }
+
+    /// Calls `f` with a reference to the current value.
+    ///
+    /// # Panics
+    /// If the value isn't set, that is, if we're not inside `scope`.
+    pub fn with<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.try_with(f).expect("task-local value not set")
+    }
+
+    /// Like `with`, but returns an error instead of panicking if the value
+    /// isn't set.
+    pub fn try_with<F, R>(&'static self, f: F) -> Result<R, AccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.inner.with(|c| match &*c.borrow() {
+            Some(value) => Ok(f(value)),
+            None => Err(AccessError(())),
+        })
+    }
+
+    /// Returns a copy of the current value.
+    ///
+    /// # Panics
+    /// If the value isn't set.
+    pub fn get(&'static self) -> T
+    where
+        T: Clone,
+    {
+        self.with(T::clone)
+    }
+}
+
+impl<T: 'static> fmt::Debug for LocalKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("LocalKey { .. }")
+    }
+}
+
+/// Returned from `LocalKey::try_with` when the value isn't set.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AccessError(());
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task-local value not set")
+    }
+}
+
+impl Error for AccessError {}
+
+/// Returned by `LocalKey::scope`.
+pub struct TaskLocalFuture<T: 'static, F: Future> {
+    local: &'static LocalKey<T>,
+    // Holds the value while the future isn't being polled
+    slot: Option<T>,
+    future: Option<Pin<Box<F>>>,
+}
+
+// The future is boxed and the value is moved in and out on every poll, so
+// nothing here is ever pinned
+impl<T: 'static, F: Future> Unpin for TaskLocalFuture<T, F> {}
+
+impl<T: 'static, F: Future> Future for TaskLocalFuture<T, F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let future = this
+            .future
+            .as_mut()
+            .expect("TaskLocalFuture polled after completion");
+
+        let res = this
+            .local
+            .scope_inner(&mut this.slot, || future.as_mut().poll(cx));
+        if res.is_ready() {
+            this.future = None;
+        }
+        res
+    }
+}
+
+impl<T: 'static, F: Future> Drop for TaskLocalFuture<T, F> {
+    fn drop(&mut self) {
+        // Destructors of the future get to see the value too
+        if let Some(future) = self.future.take() {
+            let local = self.local;
+            local.scope_inner(&mut self.slot, || drop(future));
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use super::{context::reactor, reactor::Reactor};
+
+/// Identifies a timer registered with the reactor. The sequence number makes
+/// timers with the same deadline unique.
+pub(crate) type TimerKey = (Instant, u64);
+
+/// All the timers registered with a reactor, ordered by deadline.
+/// `Driver::turn` uses the first entry to decide how long it can block in
+/// `poll`, and wakes every timer that has expired when it returns.
+#[derive(Default)]
+pub(crate) struct Timers {
+    entries: BTreeMap<TimerKey, Waker>,
+    next_seq: u64,
+}
+
+impl Timers {
+    /// Returns the key and whether this is now the earliest deadline.
+    pub(crate) fn insert(&mut self, deadline: Instant, waker: Waker) -> (TimerKey, bool) {
+        let key = (deadline, self.next_seq);
+        self.next_seq += 1;
+        self.entries.insert(key, waker);
+        let is_first = self.entries.keys().next() == Some(&key);
+        (key, is_first)
+    }
+
+    pub(crate) fn set_waker(&mut self, key: TimerKey, waker: &Waker) {
+        if let Some(w) = self.entries.get_mut(&key) {
+            if !w.will_wake(waker) {
+                *w = waker.clone();
+            }
+        }
+    }
+
+    pub(crate) fn remove(&mut self, key: TimerKey) {
+        self.entries.remove(&key);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// How long until the next timer expires. `None` if there are no timers.
+    pub(crate) fn next_timeout(&self, now: Instant) -> Option<Duration> {
+        self.entries
+            .keys()
+            .next()
+            .map(|(deadline, _)| deadline.saturating_duration_since(now))
+    }
+
+    /// Removes every expired timer and returns their wakers.
+    pub(crate) fn expired(&mut self, now: Instant) -> Vec<Waker> {
+        let pending = self.entries.split_off(&(now, u64::MAX));
+        let expired = std::mem::replace(&mut self.entries, pending);
+        expired.into_values().collect()
+    }
+}
+
+/// Waits until `duration` has elapsed.
+///
+/// # Panics
+/// When polled outside of a runtime context.
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(Instant::now() + duration)
+}
+
+/// Waits until `deadline` is reached.
+///
+/// # Panics
+/// When polled outside of a runtime context.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline,
+        registration: None,
+    }
+}
+
+/// Returned by `sleep` and `sleep_until`.
+pub struct Sleep {
+    deadline: Instant,
+    // Registered on first poll, so creating a `Sleep` doesn't need a runtime
+    registration: Option<(Arc<Reactor>, TimerKey)>,
+}
+
+impl Sleep {
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    pub fn is_elapsed(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if self.is_elapsed() {
+            if let Some((reactor, key)) = self.registration.take() {
+                reactor.remove_timer(key);
+            }
+            return Poll::Ready(());
+        }
+
+        match &self.registration {
+            // Always store the most recent waker
+            Some((reactor, key)) => reactor.set_timer_waker(*key, cx.waker()),
+            None => {
+                let reactor = reactor();
+                let key = reactor.add_timer(self.deadline, cx.waker().clone());
+                self.registration = Some((reactor, key));
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some((reactor, key)) = self.registration.take() {
+            reactor.remove_timer(key);
+        }
+    }
+}
+
+/// Creates an `Interval` that ticks right away and then every `period`.
+///
+/// # Panics
+/// If `period` is zero.
+pub fn interval(period: Duration) -> Interval {
+    assert!(period > Duration::ZERO, "`period` must be non-zero");
+    Interval {
+        next: Instant::now(),
+        period,
+    }
+}
+
+/// Returned by `interval`.
+pub struct Interval {
+    next: Instant,
+    period: Duration,
+}
+
+impl Interval {
+    /// Completes when the next tick is reached and returns the instant it
+    /// was scheduled for. If we fall behind, the missed ticks complete
+    /// right away one after another until we've caught up.
+    pub async fn tick(&mut self) -> Instant {
+        let tick = self.next;
+        sleep_until(tick).await;
+        self.next = tick + self.period;
+        tick
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+/// Returned from `timeout` when the deadline is reached before the future
+/// completes.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Elapsed(());
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+impl Error for Elapsed {}
+
+/// Runs `future` and gives up with `Err(Elapsed)` if it hasn't completed
+/// within `duration`. The future is dropped when that happens.
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: sleep(duration),
+    }
+}
+
+/// Returned by `timeout`.
+pub struct Timeout<F: Future> {
+    // Pinned whenever the `Timeout` is
+    future: F,
+    sleep: Sleep,
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        // SAFETY: `future` is never moved out of `self`, and `Timeout` has no
+        // `Drop` impl that could. `sleep` is `Unpin` and isn't pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        // Give the future a chance first, even if the deadline has passed
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+
+        match Pin::new(&mut this.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed(()))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+struct Node {
+    id: usize,
+    next: *mut Node,
+}
+
+/// A lock-free multi-producer single-consumer queue of task ids.
+///
+/// Wakers on any thread push to it, and the executor takes everything that
+/// has been pushed so far in one go. Internally it's a linked list used as a
+/// stack: a push is a single compare-and-swap on `head`, and the consumer
+/// swaps `head` for null and reverses the list it got back to restore the
+/// order the ids were pushed in. Since the consumer never takes a single
+/// node out of a list other threads can see, we don't have to worry about
+/// the ABA problem.
+pub(crate) struct WakeQueue {
+    head: AtomicPtr<Node>,
+}
+
+impl WakeQueue {
+    pub(crate) fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    pub(crate) fn push(&self, id: usize) {
+        let node = Box::into_raw(Box::new(Node {
+            id,
+            next: ptr::null_mut(),
+        }));
+
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // SAFETY: `node` isn't visible to anyone else until the CAS below
+            // succeeds
+            unsafe { (*node).next = head };
+            match self
+                .head
+                .compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Takes every id pushed so far and calls `f` with them, oldest first.
+    pub(crate) fn drain(&self, mut f: impl FnMut(usize)) {
+        let mut head = self.head.swap(ptr::null_mut(), Ordering::Acquire);
+        if head.is_null() {
+            return;
+        }
+
+        // Reverse the list so we get the ids in the order they were pushed
+        let mut prev = ptr::null_mut();
+        while !head.is_null() {
+            // SAFETY: we own every node in the list we swapped out
+            let next = unsafe { (*head).next };
+            unsafe { (*head).next = prev };
+            prev = head;
+            head = next;
+        }
+
+        let mut node = prev;
+        while !node.is_null() {
+            // SAFETY: every node was created with `Box::into_raw` in `push`
+            let boxed = unsafe { Box::from_raw(node) };
+            node = boxed.next;
+            f(boxed.id);
+        }
+    }
+}
+
+impl Drop for WakeQueue {
+    fn drop(&mut self) {
+        self.drain(|_| ());
+    }
+}
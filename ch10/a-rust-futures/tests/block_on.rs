@@ -0,0 +1,172 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use a_rust_futures::runtime::{self, Builder, Executor, Scheduling, SpawnedTasks};
+
+/// Never completes and never wakes itself.
+struct Forever;
+
+impl Future for Forever {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Self::Output> {
+        Poll::Pending
+    }
+}
+
+#[test]
+fn block_on_returns_output() {
+    let mut executor = Executor::new(Scheduling::default());
+    let out = executor.block_on(async {
+        let a = runtime::spawn(async { 20 });
+        a.await.unwrap() + 22
+    });
+    assert_eq!(out, 42);
+}
+
+#[test]
+fn block_on_waits_for_spawned_tasks_by_default() {
+    let counter = Rc::new(());
+    let counter_clone = counter.clone();
+
+    let mut executor = Executor::new(Scheduling::default());
+    executor.block_on(async move {
+        runtime::spawn(async move {
+            let _counter = counter_clone;
+        });
+    });
+
+    assert_eq!(Rc::strong_count(&counter), 1);
+}
+
+#[test]
+fn block_on_can_drop_remaining_tasks() {
+    let counter = Rc::new(());
+    let counter_clone = counter.clone();
+
+    let mut executor = Executor::new(Scheduling::default());
+    executor.on_root_complete(SpawnedTasks::Drop);
+    let out = executor.block_on(async move {
+        runtime::spawn(async move {
+            let _counter = counter_clone;
+            Forever.await;
+        });
+        "done"
+    });
+
+    assert_eq!(out, "done");
+    // The task (and what it owns) has been dropped
+    assert_eq!(Rc::strong_count(&counter), 1);
+}
+
+#[test]
+fn tasks_spawned_while_dropping_remaining_tasks_are_dropped_too() {
+    /// Spawns a task that owns `counter` when it's dropped.
+    struct SpawnOnDrop(Rc<()>);
+
+    impl Drop for SpawnOnDrop {
+        fn drop(&mut self) {
+            let counter = self.0.clone();
+            runtime::spawn(async move {
+                let _counter = counter;
+                Forever.await;
+            });
+        }
+    }
+
+    let counter = Rc::new(());
+    let counter_clone = counter.clone();
+
+    let mut executor = Executor::new(Scheduling::default());
+    executor.on_root_complete(SpawnedTasks::Drop);
+    executor.block_on(async move {
+        runtime::spawn(async move {
+            let _spawn_on_drop = SpawnOnDrop(counter_clone);
+            Forever.await;
+        });
+        // Let the task start
+        runtime::yield_now().await;
+    });
+
+    assert_eq!(Rc::strong_count(&counter), 1);
+}
+
+#[test]
+#[should_panic(expected = "the future passed to `block_on`")]
+fn lost_wakeup_in_root_future_is_reported() {
+    let mut executor = Executor::new(Scheduling::default());
+    executor.detect_lost_wakeups(true);
+    executor.block_on(Forever);
+}
+
+#[test]
+fn lost_wakeup_reports_where_the_task_was_spawned() {
+    let res = std::panic::catch_unwind(|| {
+        let mut executor = Executor::new(Scheduling::default());
+        executor.detect_lost_wakeups(true);
+        executor.block_on(async {
+            runtime::spawn(async {});
+            runtime::spawn(Forever);
+        });
+    });
+
+    let err = res.unwrap_err();
+    let msg = err.downcast_ref::<String>().unwrap();
+    assert!(msg.starts_with("lost wakeup"), "{msg}");
+    assert!(!msg.contains("task 0"), "{msg}");
+    assert!(
+        msg.contains(&format!("task 1 spawned at {}:", file!())),
+        "{msg}"
+    );
+}
+
+#[test]
+fn lost_wakeup_in_a_task_awaited_through_its_join_handle() {
+    let res = std::panic::catch_unwind(|| {
+        let mut executor = Executor::new(Scheduling::default());
+        executor.detect_lost_wakeups(true);
+        executor.block_on(async { runtime::spawn(Forever).await.unwrap() });
+    });
+
+    let err = res.unwrap_err();
+    let msg = err.downcast_ref::<String>().unwrap();
+    assert!(msg.contains("the future passed to `block_on`"), "{msg}");
+    assert!(msg.contains("task 0 spawned at"), "{msg}");
+}
+
+#[test]
+fn task_waiting_for_a_timer_is_not_a_lost_wakeup() {
+    let mut rt = Builder::new_current_thread()
+        .enable_all()
+        .detect_lost_wakeups(true)
+        .build();
+    rt.block_on(async {
+        runtime::spawn(runtime::sleep(Duration::from_millis(20)))
+            .await
+            .unwrap();
+    });
+}
+
+#[test]
+fn waker_held_by_another_thread_is_not_a_lost_wakeup() {
+    let mut executor = Executor::new(Scheduling::default());
+    executor.detect_lost_wakeups(true);
+
+    let mut started = false;
+    executor.block_on(std::future::poll_fn(|cx| {
+        if started {
+            return Poll::Ready(());
+        }
+        started = true;
+        let waker = cx.waker().clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            waker.wake();
+        });
+        Poll::Pending
+    }));
+}
@@ -0,0 +1,133 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use a_rust_futures::runtime::{
+    self,
+    net::{TcpListener, TcpStream},
+    Builder, MultiThreadHandle,
+};
+
+#[test]
+fn multi_thread_runtime_uses_thread_config() {
+    let started = Arc::new(AtomicUsize::new(0));
+    let stopped = Arc::new(AtomicUsize::new(0));
+
+    let mut rt = Builder::new_multi_thread()
+        .worker_threads(2)
+        .thread_name("pool")
+        .thread_stack_size(512 * 1024)
+        .event_capacity(1)
+        .on_thread_start({
+            let started = started.clone();
+            move || {
+                started.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .on_thread_stop({
+            let stopped = stopped.clone();
+            move || {
+                stopped.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .enable_all()
+        .build();
+
+    let names = rt.block_on(async {
+        let handles: Vec<_> = (0..10)
+            .map(|_| {
+                MultiThreadHandle::current().spawn(async {
+                    // Timers still fire with room for one event per poll
+                    runtime::sleep(Duration::from_millis(5)).await;
+                    thread::current().name().unwrap().to_string()
+                })
+            })
+            .collect();
+
+        let mut names = vec![];
+        for h in handles {
+            names.push(h.await.unwrap());
+        }
+        names
+    });
+
+    assert!(names.iter().all(|n| n == "pool-0" || n == "pool-1"));
+    drop(rt);
+    assert_eq!(started.load(Ordering::SeqCst), 2);
+    assert_eq!(stopped.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+#[should_panic(expected = "IO and time are disabled")]
+fn timers_need_enable_time() {
+    let mut rt = Builder::new().build();
+    rt.block_on(runtime::sleep(Duration::from_millis(1)));
+}
+
+#[test]
+fn reactor_on_executor_thread() {
+    let mut rt = Builder::new_current_thread()
+        .reactor_on_executor_thread()
+        .enable_all()
+        .build();
+
+    let reply = rt.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        runtime::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 5];
+            let n = stream.read(&mut buf).await.unwrap();
+            stream.write_all(&buf[..n]).await.unwrap();
+        });
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        runtime::sleep(Duration::from_millis(5)).await;
+
+        // Woken from another thread while we're waiting for events
+        let n = runtime::spawn_blocking(|| {
+            thread::sleep(Duration::from_millis(20));
+            5
+        })
+        .await
+        .unwrap();
+
+        let mut reply = vec![0u8; n];
+        let n = stream.read(&mut reply).await.unwrap();
+        reply.truncate(n);
+        reply
+    });
+    assert_eq!(reply, b"hello");
+}
+
+#[test]
+fn busy_tasks_dont_starve_the_reactor_on_executor_thread() {
+    let mut rt = Builder::new_current_thread()
+        .reactor_on_executor_thread()
+        .enable_all()
+        .build();
+
+    rt.block_on(async {
+        let done = Arc::new(AtomicBool::new(false));
+        let busy = runtime::spawn({
+            let done = done.clone();
+            async move {
+                while !done.load(Ordering::SeqCst) {
+                    runtime::yield_now().await;
+                }
+            }
+        });
+
+        // There's always a task ready, so the timer only fires if we check
+        // for events without waiting for the executor to go idle
+        runtime::sleep(Duration::from_millis(10)).await;
+        done.store(true, Ordering::SeqCst);
+        busy.await.unwrap();
+    });
+}
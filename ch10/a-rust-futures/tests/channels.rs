@@ -0,0 +1,191 @@
+use std::{thread, time::Duration};
+
+use a_rust_futures::runtime::{
+    self,
+    sync::{broadcast, mpsc, oneshot},
+    Builder, MultiThreadHandle, Runtime,
+};
+
+#[test]
+fn oneshot_delivers_the_value_or_reports_a_dropped_sender() {
+    let mut rt = Runtime::new();
+    rt.block_on(async {
+        let (tx, rx) = oneshot::channel();
+        runtime::spawn(async move {
+            runtime::sleep(Duration::from_millis(5)).await;
+            tx.send("hello").unwrap();
+        });
+        assert_eq!(rx.await, Ok("hello"));
+
+        let (tx, rx) = oneshot::channel::<u32>();
+        runtime::spawn(async move { drop(tx) });
+        assert!(rx.await.is_err());
+
+        let (tx, rx) = oneshot::channel();
+        drop(rx);
+        assert_eq!(tx.send(1), Err(1));
+    });
+}
+
+#[test]
+fn bounded_mpsc_applies_back_pressure() {
+    let mut rt = Runtime::new();
+    rt.block_on(async {
+        let (tx, mut rx) = mpsc::channel(2);
+        tx.try_send(1).unwrap();
+        tx.try_send(2).unwrap();
+        assert!(matches!(tx.try_send(3), Err(mpsc::TrySendError::Full(3))));
+
+        // Waits until the receiver makes room
+        let producer = runtime::spawn(async move {
+            for i in 3..=10 {
+                tx.send(i).await.unwrap();
+            }
+        });
+
+        let mut received = vec![];
+        while let Some(i) = rx.recv().await {
+            received.push(i);
+        }
+        producer.await.unwrap();
+        assert_eq!(received, (1..=10).collect::<Vec<_>>());
+    });
+}
+
+#[test]
+fn bounded_mpsc_serves_waiting_senders_in_order() {
+    let mut rt = Runtime::new();
+    rt.block_on(async {
+        let (tx, mut rx) = mpsc::channel(1);
+        tx.try_send(0).unwrap();
+
+        for i in 1..=3 {
+            let tx = tx.clone();
+            runtime::spawn(async move { tx.send(i).await.unwrap() });
+        }
+        // Let them all start waiting
+        runtime::yield_now().await;
+
+        let mut received = vec![rx.recv().await.unwrap()];
+        // The slot that just freed up belongs to the first waiting sender,
+        // even though it hasn't run yet
+        assert!(matches!(tx.try_send(10), Err(mpsc::TrySendError::Full(10))));
+        let late = tx.clone();
+        runtime::spawn(async move { late.send(4).await.unwrap() });
+        drop(tx);
+
+        while let Some(i) = rx.recv().await {
+            received.push(i);
+        }
+        assert_eq!(received, [0, 1, 2, 3, 4]);
+    });
+}
+
+#[test]
+fn mpsc_producers_and_consumer_on_multiple_threads() {
+    let mut rt = Builder::new_multi_thread()
+        .worker_threads(4)
+        .enable_all()
+        .build();
+
+    let sum = rt.block_on(async {
+        let (tx, mut rx) = mpsc::channel(4);
+        for p in 0..8u64 {
+            let tx = tx.clone();
+            MultiThreadHandle::current().spawn(async move {
+                for i in 0..100 {
+                    tx.send(p * 100 + i).await.unwrap();
+                }
+            });
+        }
+        drop(tx);
+
+        let consumer = MultiThreadHandle::current().spawn(async move {
+            let mut sum = 0;
+            while let Some(i) = rx.recv().await {
+                sum += i;
+            }
+            sum
+        });
+        consumer.await.unwrap()
+    });
+
+    assert_eq!(sum, (0..800).sum());
+}
+
+#[test]
+fn unbounded_mpsc_from_a_plain_thread() {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let producer = thread::spawn(move || {
+        for i in 0..50 {
+            tx.send(i).unwrap();
+            thread::sleep(Duration::from_micros(100));
+        }
+    });
+
+    let mut rt = Runtime::new();
+    let received = rt.block_on(async {
+        let mut received = vec![];
+        while let Some(i) = rx.recv().await {
+            received.push(i);
+        }
+        received
+    });
+    producer.join().unwrap();
+    assert_eq!(received, (0..50).collect::<Vec<_>>());
+}
+
+#[test]
+fn broadcast_reaches_every_receiver() {
+    let mut rt = Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build();
+
+    let results = rt.block_on(async {
+        let (tx, rx) = broadcast::channel(16);
+        let receivers: Vec<_> = std::iter::once(rx)
+            .chain((0..3).map(|_| tx.subscribe()))
+            .map(|mut rx| {
+                MultiThreadHandle::current().spawn(async move {
+                    let mut seen = vec![];
+                    while let Ok(i) = rx.recv().await {
+                        seen.push(i);
+                    }
+                    seen
+                })
+            })
+            .collect();
+
+        for i in 0..10 {
+            assert_eq!(tx.send(i).unwrap(), 4);
+            runtime::sleep(Duration::from_millis(1)).await;
+        }
+        drop(tx);
+
+        let mut results = vec![];
+        for r in receivers {
+            results.push(r.await.unwrap());
+        }
+        results
+    });
+
+    for seen in results {
+        assert_eq!(seen, (0..10).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn slow_broadcast_receiver_lags() {
+    let (tx, mut rx) = broadcast::channel(2);
+    for i in 0..5 {
+        tx.send(i).unwrap();
+    }
+
+    assert_eq!(rx.try_recv(), Err(broadcast::TryRecvError::Lagged(3)));
+    assert_eq!(rx.try_recv(), Ok(3));
+    assert_eq!(rx.try_recv(), Ok(4));
+    assert_eq!(rx.try_recv(), Err(broadcast::TryRecvError::Empty));
+    drop(tx);
+    assert_eq!(rx.try_recv(), Err(broadcast::TryRecvError::Closed));
+}
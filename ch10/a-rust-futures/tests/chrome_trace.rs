@@ -0,0 +1,65 @@
+#![cfg(feature = "chrome-trace")]
+
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use a_rust_futures::runtime::{self, chrome_trace::ChromeLayer, Builder, MultiThreadHandle};
+use tracing_subscriber::layer::SubscriberExt;
+
+#[derive(Clone, Default)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn task_polls_show_up_on_worker_threads() {
+    let buf = SharedBuf::default();
+    let (layer, guard) = ChromeLayer::new(buf.clone());
+    // The workers need to see it too, so it has to be the global default
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer)).unwrap();
+
+    let mut rt = Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build();
+    rt.block_on(async {
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                MultiThreadHandle::current().spawn(async {
+                    runtime::sleep(Duration::from_millis(5)).await;
+                })
+            })
+            .collect();
+        for h in handles {
+            h.await.unwrap();
+        }
+    });
+    drop(rt);
+    drop(guard);
+
+    let trace = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+    assert!(trace.starts_with("[\n{"));
+    assert!(trace.ends_with("}\n]\n"));
+    assert!(trace
+        .contains(r#""name":"task id=0","cat":"a_rust_futures::runtime::multi_thread","ph":"B""#));
+    assert!(trace.contains(r#""ph":"E""#));
+    assert!(trace.contains(r#""name":"thread_name","ph":"M""#));
+    assert!(
+        trace.contains(r#""args":{"name":"exec-0"}"#)
+            || trace.contains(r#""args":{"name":"exec-1"}"#)
+    );
+    assert!(trace.contains(r#""name":"woken""#));
+    assert!(trace.contains(r#""name":"completed""#));
+    assert!(trace.contains(r#""span":"task id=3""#));
+}
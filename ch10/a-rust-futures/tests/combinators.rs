@@ -0,0 +1,91 @@
+use std::{
+    cell::Cell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use a_rust_futures::{
+    future::join_all,
+    join,
+    runtime::{self, Runtime},
+    select, try_join,
+};
+
+async fn delayed<T>(ms: u64, value: T) -> T {
+    runtime::sleep(Duration::from_millis(ms)).await;
+    value
+}
+
+#[test]
+fn join_returns_every_output_and_runs_concurrently() {
+    let mut rt = Runtime::new();
+    let start = Instant::now();
+    let (a, b, c) =
+        rt.block_on(async { join!(delayed(50, 1), delayed(50, "two"), delayed(50, 3.0)).await });
+
+    assert_eq!((a, b, c), (1, "two", 3.0));
+    assert!(start.elapsed() < Duration::from_millis(140));
+}
+
+#[test]
+fn join_all_keeps_the_order() {
+    let mut rt = Runtime::new();
+    let outputs = rt.block_on(async {
+        let futures = (0..5).map(|i| delayed(50 - i * 10, i)).collect();
+        join_all(futures).await
+    });
+    assert_eq!(outputs, vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn try_join_stops_at_the_first_error() {
+    let mut rt = Runtime::new();
+    let ok =
+        rt.block_on(async { try_join!(delayed(5, Ok::<_, &str>(1)), delayed(10, Ok("b"))).await });
+    assert_eq!(ok, Ok((1, "b")));
+
+    let start = Instant::now();
+    let err = rt.block_on(async {
+        try_join!(
+            delayed(1000, Ok::<u8, _>(1)),
+            delayed(5, Err::<u8, _>("boom"))
+        )
+        .await
+    });
+    assert_eq!(err, Err("boom"));
+    assert!(start.elapsed() < Duration::from_millis(500));
+}
+
+#[test]
+fn select_cancels_the_losing_branches() {
+    struct SetOnDrop(Rc<Cell<bool>>);
+    impl Drop for SetOnDrop {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    let dropped = Rc::new(Cell::new(false));
+    let mut rt = Runtime::new();
+    let winner = rt.block_on({
+        let guard = SetOnDrop(dropped.clone());
+        let dropped = dropped.clone();
+        async move {
+            let slow = async move {
+                let _guard = guard;
+                delayed(1000, "slow").await
+            };
+
+            let winner = select! {
+                s = slow => s,
+                (f, n) = delayed(5, ("fast", 1)) => if n == 1 { f } else { "?" },
+            }
+            .await;
+
+            // Dropped as soon as the other branch won, not when we return
+            assert!(dropped.get());
+            winner
+        }
+    });
+    assert_eq!(winner, "fast");
+}
@@ -0,0 +1,69 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use a_rust_futures::runtime::{
+    self,
+    net::{TcpListener, TcpStream},
+    Runtime,
+};
+
+#[test]
+fn yield_now_lets_other_tasks_run() {
+    let mut rt = Runtime::new();
+    let log = Rc::new(RefCell::new(vec![]));
+
+    rt.block_on(async {
+        for name in ["a", "b"] {
+            let log = log.clone();
+            runtime::spawn(async move {
+                log.borrow_mut().push(format!("{name}1"));
+                runtime::yield_now().await;
+                log.borrow_mut().push(format!("{name}2"));
+            });
+        }
+    });
+
+    assert_eq!(*log.borrow(), ["a1", "b1", "a2", "b2"]);
+}
+
+#[test]
+fn always_ready_socket_does_not_starve_other_tasks() {
+    const LEN: usize = 4096;
+
+    let mut rt = Runtime::new();
+    let (reads, other_ran) = rt.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = TcpStream::connect(addr).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+
+        // Everything is in the receive buffer before the reader starts, so
+        // every read is ready right away
+        server.write_all(&[0; LEN]).await.unwrap();
+        server.shutdown().await.unwrap();
+
+        runtime::spawn(async move {
+            let mut byte = [0u8];
+            client.read(&mut byte).await.unwrap();
+
+            let other_ran = Rc::new(Cell::new(false));
+            runtime::spawn({
+                let other_ran = other_ran.clone();
+                async move { other_ran.set(true) }
+            });
+
+            let mut reads = 1;
+            while !other_ran.get() && client.read(&mut byte).await.unwrap() > 0 {
+                reads += 1;
+            }
+            (reads, other_ran.get())
+        })
+        .await
+        .unwrap()
+    });
+
+    assert!(other_ran);
+    assert!(reads < LEN, "read {reads} bytes before yielding");
+}
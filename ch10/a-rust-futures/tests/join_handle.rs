@@ -0,0 +1,53 @@
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use a_rust_futures::runtime::{self, Executor, Scheduling};
+
+/// Never completes and never wakes itself.
+struct Forever;
+
+impl Future for Forever {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Self::Output> {
+        Poll::Pending
+    }
+}
+
+#[test]
+fn join_handle_returns_task_output() {
+    let result = Rc::new(RefCell::new(vec![]));
+    let result_clone = result.clone();
+
+    let mut executor = Executor::new(Scheduling::default());
+    executor.block_on(async move {
+        let a = runtime::spawn(async { 1 + 1 });
+        let b = runtime::spawn(async { String::from("hello") });
+        let b = b.await.unwrap();
+        let a = a.await.unwrap();
+        result_clone.borrow_mut().push(format!("{a} {b}"));
+    });
+
+    assert_eq!(*result.borrow(), vec!["2 hello".to_string()]);
+}
+
+#[test]
+fn aborted_task_resolves_to_cancelled() {
+    let cancelled = Rc::new(RefCell::new(false));
+    let cancelled_clone = cancelled.clone();
+
+    let mut executor = Executor::new(Scheduling::default());
+    executor.block_on(async move {
+        let handle = runtime::spawn(Forever);
+        assert!(!handle.is_finished());
+        handle.abort();
+        let err = handle.await.unwrap_err();
+        *cancelled_clone.borrow_mut() = err.is_cancelled();
+    });
+
+    assert!(*cancelled.borrow());
+}
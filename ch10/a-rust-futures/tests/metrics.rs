@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use a_rust_futures::runtime::{
+    self,
+    net::{TcpListener, TcpStream},
+    Builder, MultiThreadHandle, Runtime,
+};
+
+#[test]
+fn current_thread_metrics() {
+    let mut rt = Runtime::new();
+    rt.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = runtime::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4];
+            stream.read(&mut buf).await.unwrap()
+        });
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        runtime::sleep(Duration::from_millis(5)).await;
+        stream.write_all(b"ping").await.unwrap();
+        assert_eq!(server.await.unwrap(), 4);
+    });
+
+    let m = rt.metrics();
+    assert!(m.reactor_polls > 0);
+    assert!(m.io_events > 0);
+    assert!(m.events_per_poll() > 0.0);
+    // At least the read and the timer. The accept only waits if it's polled
+    // before the reactor sees the connection.
+    assert!(m.wakeups >= 2);
+    assert_eq!(m.polls_ready, 1);
+    assert_eq!(m.tasks_polled, m.polls_pending + m.polls_ready);
+    let histogram_total: u64 = m.poll_durations.iter().map(|(_, n)| n).sum();
+    assert_eq!(histogram_total, m.tasks_polled);
+    assert_eq!(m.poll_durations.last().unwrap().0, Duration::MAX);
+    assert_eq!(m.workers.len(), 1);
+    assert!(m.workers[0].parks > 0);
+    assert!(m.workers[0].time_parked >= Duration::from_millis(4));
+}
+
+#[test]
+fn multi_thread_metrics() {
+    let mut rt = Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build();
+
+    let m = rt.block_on(async {
+        let handles: Vec<_> = (0..10)
+            .map(|_| {
+                MultiThreadHandle::current().spawn(async {
+                    runtime::sleep(Duration::from_millis(5)).await;
+                })
+            })
+            .collect();
+        for h in handles {
+            h.await.unwrap();
+        }
+        runtime::metrics()
+    });
+
+    assert_eq!(m.polls_ready, 10);
+    assert!(m.polls_pending >= 10);
+    assert!(m.wakeups >= 10);
+    assert_eq!(m.workers.len(), 2);
+    assert!(m.workers.iter().map(|w| w.parks).sum::<u64>() > 0);
+}
@@ -0,0 +1,111 @@
+use std::{
+    collections::HashSet,
+    future,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use a_rust_futures::runtime::{self, MultiThreadExecutor, MultiThreadHandle};
+
+#[test]
+fn spawn_from_outside_and_from_workers() {
+    let executor = MultiThreadExecutor::new(4);
+
+    let handles: Vec<_> = (0..100u64)
+        .map(|i| {
+            executor.spawn(async move {
+                // Spawning from a worker goes to that worker's local queue
+                let child = MultiThreadHandle::current().spawn(async move { i * 2 });
+                child.await.unwrap() + 1
+            })
+        })
+        .collect();
+
+    let sum = executor.block_on(async move {
+        let mut sum = 0;
+        for h in handles {
+            sum += h.await.unwrap();
+        }
+        sum
+    });
+
+    assert_eq!(sum, (0..100u64).map(|i| i * 2 + 1).sum());
+}
+
+#[test]
+fn idle_workers_steal_from_a_busy_one() {
+    let executor = MultiThreadExecutor::new(4);
+    let threads = Arc::new(Mutex::new(HashSet::new()));
+    let threads_clone = threads.clone();
+
+    executor.block_on(async move {
+        // Every task lands in the local queue of the worker running this one
+        let handles: Vec<_> = (0..40)
+            .map(|_| {
+                let threads = threads_clone.clone();
+                MultiThreadHandle::current().spawn(async move {
+                    thread::sleep(Duration::from_millis(5));
+                    let name = thread::current().name().unwrap().to_string();
+                    threads.lock().unwrap().insert(name);
+                })
+            })
+            .collect();
+
+        for h in handles {
+            h.await.unwrap();
+        }
+    });
+
+    assert!(threads.lock().unwrap().len() > 1);
+}
+
+#[test]
+fn tasks_woken_while_running_are_polled_again() {
+    let executor = MultiThreadExecutor::new(4);
+
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            executor.spawn(async {
+                for _ in 0..1000 {
+                    runtime::yield_now().await;
+                }
+            })
+        })
+        .collect();
+
+    executor.block_on(async move {
+        for h in handles {
+            h.await.unwrap();
+        }
+    });
+}
+
+#[test]
+fn executor_can_be_dropped_from_one_of_its_tasks() {
+    let slot = Arc::new(Mutex::new(None));
+    let executor = MultiThreadExecutor::new(2);
+    let (tx, rx) = mpsc::channel();
+
+    executor.spawn({
+        let slot = slot.clone();
+        async move {
+            let executor: Option<MultiThreadExecutor> = slot.lock().unwrap().take();
+            drop(executor);
+            tx.send(()).unwrap();
+            // Dropped by the worker once this poll returns
+            future::pending::<()>().await;
+        }
+    });
+    *slot.lock().unwrap() = Some(executor);
+
+    let timeout = Duration::from_secs(5);
+    assert_eq!(rx.recv_timeout(timeout), Ok(()));
+    assert_eq!(
+        rx.recv_timeout(timeout),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
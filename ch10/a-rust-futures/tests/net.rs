@@ -0,0 +1,199 @@
+use std::{
+    io,
+    os::fd::{AsRawFd, RawFd},
+    sync::Arc,
+    time::Duration,
+};
+
+use mio::{event::Source, unix::SourceFd, Interest, Registry, Token};
+
+use a_rust_futures::runtime::{
+    self,
+    net::{TcpListener, TcpStream, UdpSocket},
+    reactor, Builder, MultiThreadHandle, ReactorStats, Runtime,
+};
+
+async fn echo(stream: TcpStream) {
+    let mut buf = [0u8; 1024];
+    loop {
+        match stream.read(&mut buf).await.unwrap() {
+            0 => break,
+            n => stream.write_all(&buf[..n]).await.unwrap(),
+        }
+    }
+    stream.shutdown().await.unwrap();
+}
+
+#[test]
+fn tcp_echo() {
+    let mut rt = Runtime::new();
+    let reply = rt.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        runtime::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            echo(stream).await;
+        });
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        stream.shutdown().await.unwrap();
+
+        let mut reply = vec![];
+        stream.read_to_end(&mut reply).await.unwrap();
+        reply
+    });
+    assert_eq!(reply, b"hello");
+}
+
+#[test]
+fn tcp_read_and_write_from_different_tasks() {
+    const LEN: usize = 4 * 1024 * 1024;
+
+    let mut rt = Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build();
+
+    let received = rt.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        MultiThreadHandle::current().spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            echo(stream).await;
+        });
+
+        // Much more than fits in the socket buffers, so the writer is
+        // waiting for the socket to be writable while the reader is waiting
+        // for it to be readable
+        let stream = Arc::new(TcpStream::connect(addr).await.unwrap());
+        let writer = MultiThreadHandle::current().spawn({
+            let stream = stream.clone();
+            async move {
+                let data: Vec<u8> = (0..LEN).map(|i| i as u8).collect();
+                stream.write_all(&data).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+
+        let mut received = vec![];
+        stream.read_to_end(&mut received).await.unwrap();
+        writer.await.unwrap();
+        received
+    });
+
+    assert_eq!(received.len(), LEN);
+    assert!(received.iter().enumerate().all(|(i, b)| *b == i as u8));
+}
+
+#[test]
+fn connect_to_closed_port_fails() {
+    let mut rt = Runtime::new();
+    let res = rt.block_on(async {
+        // Bind and drop to get a port nobody listens on
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        TcpStream::connect(addr).await
+    });
+    assert!(res.is_err());
+}
+
+#[test]
+fn udp_ping_pong() {
+    let mut rt = Runtime::new();
+    rt.block_on(async {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = server.local_addr().unwrap();
+        runtime::spawn(async move {
+            let mut buf = [0u8; 64];
+            let (n, from) = server.recv_from(&mut buf).await.unwrap();
+            server.send_to(&buf[..n], from).await.unwrap();
+        });
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.connect(server_addr).unwrap();
+        client.send(b"ping").await.unwrap();
+
+        let mut buf = [0u8; 64];
+        let n = client.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"ping");
+    });
+}
+
+#[test]
+fn dropped_sockets_leave_nothing_in_the_reactor() {
+    let mut rt = Runtime::new();
+    rt.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = TcpStream::connect(addr).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        assert_eq!(reactor().stats().registrations, 3);
+
+        // Nothing is ever written, so the read is dropped while waiting
+        let mut buf = [0u8; 16];
+        let res = runtime::timeout(Duration::from_millis(20), server.read(&mut buf)).await;
+        assert!(res.is_err());
+
+        drop((listener, client, server));
+        assert_eq!(
+            reactor().stats(),
+            ReactorStats {
+                registrations: 0,
+                timers: 0
+            }
+        );
+    });
+}
+
+/// A plain std socket, so the reactor is the only thing keeping track of
+/// whether it's registered.
+struct StdSocket(std::net::UdpSocket);
+
+impl AsRawFd for StdSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl Source for StdSocket {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).deregister(registry)
+    }
+}
+
+#[test]
+fn dropped_registration_deregisters_the_source() {
+    let mut rt = Runtime::new();
+    rt.block_on(async {
+        let mut socket = StdSocket(std::net::UdpSocket::bind("127.0.0.1:0").unwrap());
+        let registration = reactor().register(&mut socket, Interest::READABLE).unwrap();
+        // The OS poller doesn't take the same file descriptor twice
+        assert!(reactor().register(&mut socket, Interest::READABLE).is_err());
+        assert_eq!(reactor().stats().registrations, 1);
+
+        // Without calling `deregister` first
+        drop(registration);
+        assert!(reactor().register(&mut socket, Interest::READABLE).is_ok());
+    });
+}
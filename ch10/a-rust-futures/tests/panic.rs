@@ -0,0 +1,59 @@
+use std::{
+    panic,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use a_rust_futures::runtime::{self, Builder, MultiThreadExecutor};
+
+#[test]
+fn panicking_task_does_not_take_down_the_executor() {
+    let hook_calls = Arc::new(AtomicUsize::new(0));
+    let hook_calls_clone = hook_calls.clone();
+
+    let mut rt = Builder::new()
+        .on_task_panic(move |_| {
+            hook_calls_clone.fetch_add(1, Ordering::SeqCst);
+        })
+        .build();
+
+    let (panicked, other) = rt.block_on(async {
+        let panicked = runtime::spawn(async {
+            panic!("boom");
+        });
+        let other = runtime::spawn(async { 42 });
+        (panicked.await, other.await)
+    });
+
+    let err = panicked.unwrap_err();
+    assert!(err.is_panic());
+    assert_eq!(*err.into_panic().downcast::<&str>().unwrap(), "boom");
+    assert_eq!(other.unwrap(), 42);
+    assert_eq!(hook_calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn panicking_task_does_not_kill_a_worker() {
+    let executor = MultiThreadExecutor::new(1);
+
+    let err = executor.block_on(async {
+        runtime::MultiThreadHandle::current()
+            .spawn(async { panic!("boom") })
+            .await
+    });
+    assert!(err.unwrap_err().is_panic());
+
+    // The only worker is still alive
+    assert_eq!(executor.block_on(async { 1 }), 1);
+}
+
+#[test]
+fn root_future_panic_propagates() {
+    let mut rt = Builder::new().build();
+    let res = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        rt.block_on(async { panic!("root") });
+    }));
+    assert!(res.is_err());
+}
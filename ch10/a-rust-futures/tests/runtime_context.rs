@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use a_rust_futures::runtime::{self, Handle, Runtime};
+
+#[test]
+fn runtimes_are_independent() {
+    assert!(Handle::try_current().is_none());
+
+    let mut rt1 = Runtime::new();
+    let mut rt2 = Runtime::new();
+
+    let r1 = rt1.block_on(async { runtime::reactor() });
+    let r2 = rt2.block_on(async { runtime::reactor() });
+    assert!(!Arc::ptr_eq(&r1, &r2));
+
+    // Nested: the inner runtime is current until its `block_on` returns
+    let (inner, outer) = rt1.block_on(async move {
+        let inner = rt2.block_on(async { runtime::reactor() });
+        (inner, runtime::reactor())
+    });
+    assert!(Arc::ptr_eq(&inner, &r2));
+    assert!(Arc::ptr_eq(&outer, &r1));
+
+    assert!(Handle::try_current().is_none());
+}
+
+#[test]
+fn runtime_can_be_restarted() {
+    for i in 0..3 {
+        let mut rt = Runtime::new();
+        assert_eq!(rt.block_on(async move { i }), i);
+        rt.shutdown(Default::default());
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use a_rust_futures::runtime::{self, Executor, Scheduling};
+
+/// Wakes itself and returns `Pending` `n` times before it's ready, so the
+/// task goes straight back in the ready queue every time it's polled.
+struct SelfWaking {
+    id: usize,
+    remaining: usize,
+    polls: Rc<RefCell<Vec<usize>>>,
+}
+
+impl Future for SelfWaking {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.polls.borrow_mut().push(self.id);
+        if self.remaining == 0 {
+            return Poll::Ready(());
+        }
+        self.remaining -= 1;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Spawns `n_tasks` self waking tasks and returns the order they were polled
+/// in.
+fn run(scheduling: Scheduling, n_tasks: usize, n_yields: usize) -> Vec<usize> {
+    let polls = Rc::new(RefCell::new(vec![]));
+    let polls_clone = polls.clone();
+
+    let mut executor = Executor::new(scheduling);
+    executor.block_on(async move {
+        for id in 0..n_tasks {
+            runtime::spawn(SelfWaking {
+                id,
+                remaining: n_yields,
+                polls: polls_clone.clone(),
+            });
+        }
+    });
+
+    let polls = polls.borrow().clone();
+    assert_eq!(polls.len(), n_tasks * (n_yields + 1));
+    polls
+}
+
+/// The largest difference between how many times the most and least polled
+/// tasks have been polled at any point.
+fn max_lag(polls: &[usize], n_tasks: usize) -> usize {
+    let mut counts = vec![0usize; n_tasks];
+    let mut lag = 0;
+    for (i, &id) in polls.iter().enumerate() {
+        counts[id] += 1;
+        // Only look at points where every task has been spawned and polled
+        // once and none have finished
+        if i >= n_tasks && counts.iter().all(|&c| c > 0) {
+            let max = counts.iter().max().unwrap();
+            let min = counts.iter().min().unwrap();
+            lag = lag.max(max - min);
+        }
+    }
+    lag
+}
+
+#[test]
+fn fifo_is_fair_to_self_waking_tasks() {
+    let n_tasks = 100;
+    let polls = run(Scheduling::Fifo, n_tasks, 20);
+    assert!(max_lag(&polls, n_tasks) <= 1);
+}
+
+#[test]
+fn lifo_slot_does_not_starve_the_queue() {
+    let n_tasks = 100;
+    let polls = run(Scheduling::LifoSlot, n_tasks, 20);
+
+    // A task can hold on to the LIFO slot for a few polls in a row, but
+    // never more than that
+    let longest_run = polls
+        .chunk_by(|a, b| a == b)
+        .map(|run| run.len())
+        .max()
+        .unwrap();
+    assert!(longest_run <= 4, "longest run: {longest_run}");
+    assert!(max_lag(&polls, n_tasks) <= 4);
+}
+
+#[test]
+fn priority_runs_high_priority_tasks_first() {
+    let order = Rc::new(RefCell::new(vec![]));
+    let order_clone = order.clone();
+
+    let mut executor = Executor::new(Scheduling::Priority);
+    executor.block_on(async move {
+        for (name, priority) in [("low", 0), ("high", 10), ("medium", 5)] {
+            let order = order_clone.clone();
+            runtime::spawn_with_priority(async move { order.borrow_mut().push(name) }, priority);
+        }
+    });
+
+    assert_eq!(*order.borrow(), vec!["high", "medium", "low"]);
+}
@@ -0,0 +1,77 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use a_rust_futures::runtime::{self, Builder};
+
+/// Never completes and never wakes itself, like a task that lost its wakeup.
+struct Forever;
+
+impl Future for Forever {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Self::Output> {
+        Poll::Pending
+    }
+}
+
+struct SetOnDrop(Arc<AtomicBool>);
+
+impl Drop for SetOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn shutdown_drops_pending_tasks_and_stops_reactor() {
+    let stuck_dropped = Arc::new(AtomicBool::new(false));
+    let finished = Arc::new(AtomicBool::new(false));
+
+    let mut rt = runtime::init();
+    let out = rt.block_on(async { 1 });
+    assert_eq!(out, 1);
+
+    let guard = SetOnDrop(stuck_dropped.clone());
+    let stuck = rt.spawn(async move {
+        let _guard = guard;
+        Forever.await;
+    });
+
+    let finished_clone = finished.clone();
+    rt.spawn(async move { finished_clone.store(true, Ordering::SeqCst) });
+
+    // Returns, even though one task can never complete
+    rt.shutdown(Duration::from_millis(50));
+
+    assert!(finished.load(Ordering::SeqCst));
+    assert!(stuck_dropped.load(Ordering::SeqCst));
+    assert!(stuck.is_finished());
+}
+
+#[test]
+fn multi_thread_shutdown_returns_once_tasks_are_done() {
+    let rt = Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build();
+    let finished = Arc::new(AtomicBool::new(false));
+    rt.spawn({
+        let finished = finished.clone();
+        async move {
+            runtime::sleep(Duration::from_millis(20)).await;
+            finished.store(true, Ordering::SeqCst);
+        }
+    });
+
+    let start = Instant::now();
+    rt.shutdown(Duration::from_secs(10));
+    assert!(finished.load(Ordering::SeqCst));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,83 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use a_rust_futures::runtime::{self, Builder, Runtime};
+
+#[test]
+fn spawn_blocking_returns_result_without_blocking_the_executor() {
+    let mut rt = Runtime::new();
+    let (blocking, other) = rt.block_on(async {
+        let blocking = runtime::spawn_blocking(|| {
+            thread::sleep(Duration::from_millis(50));
+            thread::current().name().unwrap().to_string()
+        });
+
+        // Runs to completion while the blocking job is still sleeping
+        let other = runtime::spawn(async { 1 }).await.unwrap();
+        (blocking.await.unwrap(), other)
+    });
+
+    assert_eq!(blocking, "blocking");
+    assert_eq!(other, 1);
+}
+
+#[test]
+fn blocking_pool_is_bounded() {
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+
+    let mut rt = Builder::new().max_blocking_threads(3).build();
+    rt.block_on({
+        let running = running.clone();
+        let max_running = max_running.clone();
+        async move {
+            let handles: Vec<_> = (0..12)
+                .map(|_| {
+                    let running = running.clone();
+                    let max_running = max_running.clone();
+                    runtime::spawn_blocking(move || {
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_running.fetch_max(now, Ordering::SeqCst);
+                        thread::sleep(Duration::from_millis(10));
+                        running.fetch_sub(1, Ordering::SeqCst);
+                    })
+                })
+                .collect();
+
+            for h in handles {
+                h.await.unwrap();
+            }
+        }
+    });
+
+    assert_eq!(max_running.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn idle_blocking_threads_are_reused() {
+    let mut rt = Builder::new()
+        .thread_keep_alive(Duration::from_secs(5))
+        .enable_time()
+        .build();
+
+    let ids = rt.block_on(async {
+        let mut ids = vec![];
+        for _ in 0..3 {
+            let id = runtime::spawn_blocking(|| thread::current().id())
+                .await
+                .unwrap();
+            ids.push(id);
+            // Give the thread time to go back to being idle
+            runtime::sleep(Duration::from_millis(10)).await;
+        }
+        ids
+    });
+
+    assert!(ids.windows(2).all(|w| w[0] == w[1]));
+}
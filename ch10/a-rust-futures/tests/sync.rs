@@ -0,0 +1,274 @@
+//! The primitives are mostly exercised on the multi-thread runtime and in a
+//! loop, to shake out lost wakeups and races that only show up sometimes.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use a_rust_futures::runtime::{
+    self,
+    sync::{Mutex, Notify, RwLock, Semaphore},
+    Builder, MultiThreadHandle, Runtime,
+};
+
+const ITERATIONS: usize = 20;
+
+fn multi_thread() -> Runtime {
+    Builder::new_multi_thread()
+        .worker_threads(4)
+        .enable_all()
+        .build()
+}
+
+#[test]
+fn mutex_guard_held_across_await() {
+    for _ in 0..ITERATIONS {
+        let mut rt = multi_thread();
+        let total = rt.block_on(async {
+            let counter = Arc::new(Mutex::new(0));
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    let counter = counter.clone();
+                    MultiThreadHandle::current().spawn(async move {
+                        for _ in 0..100 {
+                            let mut guard = counter.lock().await;
+                            let seen = *guard;
+                            // Anyone sneaking in here would make us lose an
+                            // increment
+                            runtime::yield_now().await;
+                            *guard = seen + 1;
+                        }
+                    })
+                })
+                .collect();
+
+            for h in handles {
+                h.await.unwrap();
+            }
+            let total = *counter.lock().await;
+            total
+        });
+        assert_eq!(total, 800);
+    }
+}
+
+#[test]
+fn mutex_is_fair() {
+    let mut rt = Runtime::new();
+    let order = rt.block_on(async {
+        let mutex = Arc::new(Mutex::new(vec![]));
+        let guard = mutex.lock().await;
+
+        let handles: Vec<_> = (0..5)
+            .map(|i| {
+                let mutex = mutex.clone();
+                runtime::spawn(async move { mutex.lock().await.push(i) })
+            })
+            .collect();
+
+        // Let them all get in line, in spawn order
+        runtime::sleep(Duration::from_millis(5)).await;
+        assert!(mutex.try_lock().is_none());
+        drop(guard);
+
+        for h in handles {
+            h.await.unwrap();
+        }
+        let order = mutex.lock().await.clone();
+        order
+    });
+    assert_eq!(order, vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn rwlock_readers_share_and_writers_are_exclusive() {
+    for _ in 0..ITERATIONS {
+        let mut rt = multi_thread();
+        rt.block_on(async {
+            let lock = Arc::new(RwLock::new(0));
+            let readers = Arc::new(AtomicUsize::new(0));
+            let max_readers = Arc::new(AtomicUsize::new(0));
+
+            let handles: Vec<_> = (0..16)
+                .map(|i| {
+                    let lock = lock.clone();
+                    let readers = readers.clone();
+                    let max_readers = max_readers.clone();
+                    MultiThreadHandle::current().spawn(async move {
+                        if i % 4 == 0 {
+                            let mut guard = lock.write().await;
+                            assert_eq!(readers.load(Ordering::SeqCst), 0);
+                            runtime::yield_now().await;
+                            *guard += 1;
+                        } else {
+                            let guard = lock.read().await;
+                            let now = readers.fetch_add(1, Ordering::SeqCst) + 1;
+                            max_readers.fetch_max(now, Ordering::SeqCst);
+                            runtime::sleep(Duration::from_millis(1)).await;
+                            readers.fetch_sub(1, Ordering::SeqCst);
+                            drop(guard);
+                        }
+                    })
+                })
+                .collect();
+
+            for h in handles {
+                h.await.unwrap();
+            }
+            assert_eq!(*lock.read().await, 4);
+            assert!(max_readers.load(Ordering::SeqCst) > 1);
+        });
+    }
+}
+
+#[test]
+fn waiting_writer_is_not_starved_by_new_readers() {
+    let mut rt = Runtime::new();
+    rt.block_on(async {
+        let lock = Arc::new(RwLock::new(()));
+        let read = lock.read().await;
+
+        let writer = runtime::spawn({
+            let lock = lock.clone();
+            async move {
+                let _guard = lock.write().await;
+            }
+        });
+        runtime::sleep(Duration::from_millis(5)).await;
+
+        // The writer is in line, so a new reader has to wait behind it
+        assert!(lock.try_read().is_none());
+        drop(read);
+        writer.await.unwrap();
+        assert!(lock.try_read().is_some());
+    });
+}
+
+#[test]
+fn semaphore_limits_concurrency() {
+    for _ in 0..ITERATIONS {
+        let mut rt = multi_thread();
+        let max_running = rt.block_on(async {
+            let semaphore = Arc::new(Semaphore::new(3));
+            let running = Arc::new(AtomicUsize::new(0));
+            let max_running = Arc::new(AtomicUsize::new(0));
+
+            let handles: Vec<_> = (0..20)
+                .map(|_| {
+                    let semaphore = semaphore.clone();
+                    let running = running.clone();
+                    let max_running = max_running.clone();
+                    MultiThreadHandle::current().spawn(async move {
+                        let _permit = semaphore.acquire().await;
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_running.fetch_max(now, Ordering::SeqCst);
+                        runtime::yield_now().await;
+                        running.fetch_sub(1, Ordering::SeqCst);
+                    })
+                })
+                .collect();
+
+            for h in handles {
+                h.await.unwrap();
+            }
+            assert_eq!(semaphore.available_permits(), 3);
+            max_running.load(Ordering::SeqCst)
+        });
+        assert!(max_running <= 3);
+    }
+}
+
+#[test]
+fn cancelled_acquire_passes_permits_on() {
+    let mut rt = Runtime::new();
+    rt.block_on(async {
+        let semaphore = Arc::new(Semaphore::new(1));
+        let permit = semaphore.acquire().await;
+
+        let first = runtime::spawn({
+            let semaphore = semaphore.clone();
+            async move {
+                let _permit = semaphore.acquire().await;
+            }
+        });
+        let second = runtime::spawn({
+            let semaphore = semaphore.clone();
+            async move {
+                let _permit = semaphore.acquire().await;
+            }
+        });
+        runtime::sleep(Duration::from_millis(5)).await;
+
+        // The first in line gives up after being handed the permit
+        drop(permit);
+        first.abort();
+        assert!(first.await.unwrap_err().is_cancelled());
+        second.await.unwrap();
+        assert_eq!(semaphore.available_permits(), 1);
+    });
+}
+
+#[test]
+fn notify_one_wakes_in_order_and_stores_a_permit() {
+    let mut rt = Runtime::new();
+    rt.block_on(async {
+        let notify = Notify::new();
+        // Nobody is waiting, so this is remembered
+        notify.notify_one();
+        notify.notified().await;
+
+        let notify = Arc::new(notify);
+        let woken = Arc::new(Mutex::new(vec![]));
+        let handles: Vec<_> = (0..3)
+            .map(|i| {
+                let notify = notify.clone();
+                let woken = woken.clone();
+                runtime::spawn(async move {
+                    notify.notified().await;
+                    woken.lock().await.push(i);
+                })
+            })
+            .collect();
+        runtime::sleep(Duration::from_millis(5)).await;
+
+        for _ in 0..3 {
+            notify.notify_one();
+            runtime::yield_now().await;
+        }
+        for h in handles {
+            h.await.unwrap();
+        }
+        assert_eq!(*woken.lock().await, vec![0, 1, 2]);
+    });
+}
+
+#[test]
+fn notify_waiters_wakes_everyone_waiting() {
+    for _ in 0..ITERATIONS {
+        let mut rt = multi_thread();
+        rt.block_on(async {
+            let notify = Arc::new(Notify::new());
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    let notify = notify.clone();
+                    MultiThreadHandle::current().spawn(async move { notify.notified().await })
+                })
+                .collect();
+
+            // A task only counts as waiting once `notified` has been polled,
+            // and we don't know when that happens on the workers, so keep
+            // notifying until everyone is through
+            for h in handles {
+                while !h.is_finished() {
+                    notify.notify_waiters();
+                    runtime::sleep(Duration::from_millis(1)).await;
+                }
+                h.await.unwrap();
+            }
+        });
+    }
+}
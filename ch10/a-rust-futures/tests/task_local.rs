@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use a_rust_futures::runtime::{self, Runtime};
+
+a_rust_futures::task_local! {
+    static REQUEST_ID: u32;
+    static NAME: String;
+}
+
+#[test]
+fn tasks_on_the_same_thread_see_their_own_value() {
+    let mut rt = Runtime::new();
+    let seen = rt.block_on(async {
+        let handles: Vec<_> = (0..5)
+            .map(|i| {
+                runtime::spawn(REQUEST_ID.scope(i, async move {
+                    let mut seen = vec![];
+                    for _ in 0..3 {
+                        // Lets the other tasks run in between
+                        runtime::sleep(Duration::from_millis(1)).await;
+                        seen.push(REQUEST_ID.get());
+                    }
+                    (i, seen)
+                }))
+            })
+            .collect();
+
+        let mut seen = vec![];
+        for h in handles {
+            seen.push(h.await.unwrap());
+        }
+        seen
+    });
+
+    for (i, values) in seen {
+        assert_eq!(values, vec![i; 3]);
+    }
+    assert!(REQUEST_ID.try_with(|_| ()).is_err());
+}
+
+#[test]
+fn scopes_nest_and_are_not_inherited_by_spawned_tasks() {
+    let mut rt = Runtime::new();
+    rt.block_on(NAME.scope("outer".to_string(), async {
+        NAME.scope("inner".to_string(), async {
+            assert_eq!(NAME.get(), "inner");
+        })
+        .await;
+        assert_eq!(NAME.get(), "outer");
+
+        let child = runtime::spawn(async { NAME.try_with(|n| n.clone()) });
+        assert!(child.await.unwrap().is_err());
+
+        assert_eq!(NAME.sync_scope("sync".to_string(), || NAME.get()), "sync");
+        NAME.with(|n| assert_eq!(n, "outer"));
+    }));
+}
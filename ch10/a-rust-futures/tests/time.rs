@@ -0,0 +1,73 @@
+use std::time::{Duration, Instant};
+
+use a_rust_futures::runtime::{self, Runtime};
+
+#[test]
+fn sleep_waits_at_least_the_duration() {
+    let mut rt = Runtime::new();
+    let elapsed = rt.block_on(async {
+        let start = Instant::now();
+        runtime::sleep(Duration::from_millis(50)).await;
+        start.elapsed()
+    });
+    assert!(elapsed >= Duration::from_millis(50), "{elapsed:?}");
+}
+
+#[test]
+fn sleeps_wake_in_deadline_order() {
+    let mut rt = Runtime::new();
+    let order = rt.block_on(async {
+        // Spawned longest first, so every new timer is the earliest one and
+        // has to interrupt the event loop
+        let handles: Vec<_> = [60, 40, 20]
+            .into_iter()
+            .map(|ms| {
+                runtime::spawn(async move {
+                    runtime::sleep(Duration::from_millis(ms)).await;
+                    Instant::now()
+                })
+            })
+            .collect();
+
+        let mut done = vec![];
+        for h in handles {
+            done.push(h.await.unwrap());
+        }
+        done
+    });
+    assert!(order[2] <= order[1] && order[1] <= order[0]);
+}
+
+#[test]
+fn interval_ticks_every_period() {
+    let mut rt = Runtime::new();
+    let ticks = rt.block_on(async {
+        let mut interval = runtime::interval(Duration::from_millis(10));
+        let mut ticks = vec![];
+        for _ in 0..4 {
+            ticks.push(interval.tick().await);
+        }
+        ticks
+    });
+
+    for pair in ticks.windows(2) {
+        assert_eq!(pair[1] - pair[0], Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn timeout_elapses_or_returns_output() {
+    let mut rt = Runtime::new();
+    let (slow, fast) = rt.block_on(async {
+        let slow = runtime::timeout(
+            Duration::from_millis(10),
+            runtime::sleep(Duration::from_secs(10)),
+        )
+        .await;
+        let fast = runtime::timeout(Duration::from_secs(10), async { 5 }).await;
+        (slow, fast)
+    });
+
+    assert!(slow.is_err());
+    assert_eq!(fast, Ok(5));
+}